@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::{env, fs};
+
+use crate::nvy_config::{config_path, does_config_exist, load_config_from, resolve_profile, write_atomic, CONFIG_FILE_NAME};
+use crate::success;
+
+/// The configs that the shell hook may apply, as `<sha256> <absolute path>` lines, kept in the
+/// user's config directory rather than in any project. The hash covers the config and the files
+/// of its current profiles.
+const ALLOW_FILE_PATH: &str = "nvy/allowed";
+
+/// Let the shell hook apply this config's current profiles, as long as they stay unchanged
+pub fn run_allow() -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let path = allow_config()?;
+    success!("Allowed {}, its profiles will be applied the next time you enter its directory.", path);
+    Ok(())
+}
+
+/// Whether the config and the files of its current profiles, exactly as they are now, have been allowed
+pub(crate) fn is_config_allowed() -> Result<bool> {
+    let (path, hash) = config_entry()?;
+    Ok(read_allow_list()?.get(&path) == Some(&hash))
+}
+
+/// Allow the config as it is now, replacing any earlier version of it
+pub(crate) fn allow_config() -> Result<String> {
+    let (path, hash) = config_entry()?;
+    let mut allowed = read_allow_list()?;
+    allowed.insert(path.clone(), hash);
+
+    let allow_file = allow_file()?;
+    if let Some(dir) = allow_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let lines: String = allowed.iter().map(|(path, hash)| format!("{} {}\n", hash, path)).collect();
+    write_atomic(&allow_file.to_string_lossy(), lines)?;
+    Ok(path)
+}
+
+/// Make a change with nvy itself, which shouldn't stop the shell hook from applying the config
+pub(crate) fn keep_allowed<T>(change: impl FnOnce() -> Result<T>) -> Result<T> {
+    let allowed = does_config_exist() && is_config_allowed().unwrap_or(false);
+    let result = change()?;
+    if allowed {
+        allow_config()?;
+    }
+    Ok(result)
+}
+
+/// The config's absolute path, and a hash of it along with every file its current profiles
+/// are exported from, so that a change to any of them has to be allowed again
+fn config_entry() -> Result<(String, String)> {
+    let path = env::current_dir()?.join(config_path());
+    let mut hasher = Sha256::new();
+    hasher.update(fs::read(&path)?);
+
+    let config = load_config_from(&path)?;
+    let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+    for profile in &config.current_profiles {
+        // A profile that can't be resolved can't be exported either, so there is nothing to hash
+        for layer in resolve_profile(&config, profile).unwrap_or_default() {
+            hasher.update(layer.path.as_bytes());
+            match fs::read(dir.join(&layer.path)) {
+                Ok(contents) => {
+                    hasher.update([1]);
+                    hasher.update((contents.len() as u64).to_le_bytes());
+                    hasher.update(contents);
+                }
+                Err(_) => hasher.update([0]),
+            }
+        }
+    }
+    Ok((path.to_string_lossy().into_owned(), format!("{:x}", hasher.finalize())))
+}
+
+fn read_allow_list() -> Result<BTreeMap<String, String>> {
+    let allow_file = allow_file()?;
+    if !allow_file.is_file() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(fs::read_to_string(allow_file)?
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(hash, path)| (path.to_string(), hash.to_string()))
+        .collect())
+}
+
+fn allow_file() -> Result<PathBuf> {
+    Ok(user_config_dir()?.join(ALLOW_FILE_PATH))
+}
+
+/// `$XDG_CONFIG_HOME`, or else `~/.config` (`%APPDATA%` on Windows)
+fn user_config_dir() -> Result<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        });
+    dir.ok_or_else(|| anyhow!("Couldn't find your config directory, set XDG_CONFIG_HOME to it."))
+}
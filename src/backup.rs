@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::nvy_config::{
+    create_nvy_dir, does_config_exist, does_file_exist, is_target_shell, load_config, write_atomic, CONFIG_FILE_NAME,
+};
+use crate::success;
+
+/// Previous contents of the target are kept here before it is overwritten
+pub const BACKUP_DIR: &str = ".nvy/backups";
+
+/// How many backups of a target are kept, the oldest ones are removed first
+const MAX_BACKUPS: usize = 10;
+
+/// Roll the target back to the contents it had before the nth most recent overwrite
+pub fn run_restore(n: usize, list: bool) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    if is_target_shell(&config) {
+        return Err(anyhow!("The target is the shell, so there is nothing to restore."));
+    }
+
+    let backups = list_backups(&config.target)?;
+    if list {
+        if backups.is_empty() {
+            println!("No backups of {}.", config.target);
+        }
+        for (i, backup) in backups.iter().enumerate() {
+            println!("{}: {}", i + 1, backup.display());
+        }
+        return Ok(());
+    }
+
+    if backups.is_empty() {
+        return Err(anyhow!("There are no backups of {} to restore.", config.target));
+    }
+    let Some(backup) = n.checked_sub(1).and_then(|i| backups.get(i)) else {
+        return Err(anyhow!(
+            "There are only {} backup(s) of {}, pick one from 1 to {}.",
+            backups.len(),
+            config.target,
+            backups.len()
+        ));
+    };
+
+    let content = fs::read_to_string(backup)?;
+    // Keep what is being replaced too, so that the restore can be undone
+    backup_target(&config.target, &content)?;
+    write_atomic(&config.target, content)?;
+    success!("Restored {} from {}", config.target, backup.display());
+    Ok(())
+}
+
+/// Save the current contents of the target before it is overwritten with new content,
+/// unless it doesn't exist yet or wouldn't change
+pub(crate) fn backup_target(target: &str, new_content: &str) -> Result<()> {
+    if !does_file_exist(target) {
+        return Ok(());
+    }
+    let current = fs::read_to_string(target)?;
+    if current == new_content {
+        return Ok(());
+    }
+
+    create_nvy_dir()?;
+    fs::create_dir_all(BACKUP_DIR)?;
+    let mut timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let mut path = backup_path(target, timestamp);
+    while path.exists() {
+        timestamp += 1;
+        path = backup_path(target, timestamp);
+    }
+    // Copying keeps the target's permissions, so a backup is never readable by more users than it
+    fs::copy(target, &path)?;
+
+    for old in list_backups(target)?.iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Backups are named after the target, followed by the time they were taken
+fn backup_path(target: &str, timestamp: u128) -> PathBuf {
+    Path::new(BACKUP_DIR).join(format!("{}.{}", backup_prefix(target), timestamp))
+}
+
+fn backup_prefix(target: &str) -> String {
+    target.trim_start_matches("./").replace(['/', '\\'], "_")
+}
+
+/// The backups of a target, most recent first
+fn list_backups(target: &str) -> Result<Vec<PathBuf>> {
+    if !Path::new(BACKUP_DIR).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}.", backup_prefix(target));
+    let mut backups: Vec<(u128, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(BACKUP_DIR)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if let Some(timestamp) = name.strip_prefix(&prefix).and_then(|rest| rest.parse().ok()) {
+            backups.push((timestamp, path));
+        }
+    }
+
+    backups.sort_by_key(|(timestamp, _)| Reverse(*timestamp));
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_path_should_flatten_target_path() {
+        assert_eq!(backup_path(".env.nvy", 42), Path::new(".nvy/backups/.env.nvy.42"));
+        assert_eq!(backup_path("./config/app.json", 42), Path::new(".nvy/backups/config_app.json.42"));
+    }
+}
@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::env::EnvFile;
+use crate::nvy_config::{does_config_exist, does_file_exist, load_config, CONFIG_FILE_NAME, EXAMPLE_FILE_NAME};
+use crate::r#use::export_profiles;
+use crate::{error, success};
+
+/// The type a variable's value is expected to have, declared under `types` in nvy.yaml
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TypeHint {
+    Named(NamedType),
+    /// One of a fixed list of values
+    Enum(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedType {
+    Url,
+    Int,
+    Bool,
+}
+
+impl TypeHint {
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            TypeHint::Named(NamedType::Url) => is_url(value),
+            TypeHint::Named(NamedType::Int) => value.parse::<i64>().is_ok(),
+            TypeHint::Named(NamedType::Bool) => {
+                matches!(value.to_lowercase().as_str(), "true" | "false" | "1" | "0" | "yes" | "no")
+            }
+            TypeHint::Enum(values) => values.iter().any(|v| v == value),
+        }
+    }
+}
+
+impl fmt::Display for TypeHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeHint::Named(NamedType::Url) => write!(f, "a url"),
+            TypeHint::Named(NamedType::Int) => write!(f, "an int"),
+            TypeHint::Named(NamedType::Bool) => write!(f, "a bool"),
+            TypeHint::Enum(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+
+/// Check the given profiles (or every profile) against the keys declared in `.env.example`
+/// and the types in nvy.yaml, failing if any of them don't match
+pub fn run_check(profiles: &[String]) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    if !does_file_exist(EXAMPLE_FILE_NAME) {
+        return Err(anyhow!("{} does not exist in the current directory, so there is nothing to check against.", EXAMPLE_FILE_NAME));
+    }
+
+    let config = load_config()?;
+    let example = EnvFile::read(EXAMPLE_FILE_NAME)?;
+    let declared: Vec<&str> = example.vars().map(|var| var.key()).collect();
+
+    let profiles = if profiles.is_empty() {
+        config.profiles.keys().cloned().collect()
+    } else {
+        profiles.to_vec()
+    };
+
+    let mut problem_count = 0;
+    for profile in &profiles {
+        let result = export_profiles(&config, std::slice::from_ref(profile), false)?;
+        let vars: BTreeMap<&str, &str> = result
+            .new_vars
+            .iter()
+            .map(|var| (var.key.as_str(), var.value.as_str()))
+            .collect();
+
+        let problems = check_vars(&vars, &declared, &config.types);
+        for problem in &problems {
+            error!("{}: {}", profile, problem);
+        }
+        problem_count += problems.len();
+    }
+
+    if problem_count > 0 {
+        return Err(anyhow!("Found {} problem(s) checking against {}.", problem_count, EXAMPLE_FILE_NAME));
+    }
+
+    success!("Profile(s) {} match {}", profiles.join(", "), EXAMPLE_FILE_NAME);
+    Ok(())
+}
+
+fn check_vars(vars: &BTreeMap<&str, &str>, declared: &[&str], types: &BTreeMap<String, TypeHint>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for key in declared {
+        if !vars.contains_key(key) {
+            problems.push(format!("{} is missing", key));
+        }
+    }
+
+    for (key, value) in vars {
+        if !declared.contains(key) {
+            problems.push(format!("{} is not declared in {}", key, EXAMPLE_FILE_NAME));
+        }
+        if let Some(hint) = types.get(*key) {
+            if !hint.matches(value) {
+                problems.push(format!("{} is not {}", key, hint));
+            }
+        }
+    }
+
+    problems
+}
+
+/// A scheme followed by `://` and something after it, e.g. `postgres://localhost`
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        && !rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_hint_should_parse_from_yaml() {
+        let types: BTreeMap<String, TypeHint> =
+            serde_yaml::from_str("PORT: int\nDEBUG: bool\nLOG_LEVEL: [debug, info]").unwrap();
+        assert_eq!(types["PORT"], TypeHint::Named(NamedType::Int));
+        assert_eq!(types["DEBUG"], TypeHint::Named(NamedType::Bool));
+        assert_eq!(types["LOG_LEVEL"], TypeHint::Enum(vec!["debug".to_string(), "info".to_string()]));
+    }
+
+    #[test]
+    fn test_type_hint_should_match_values() {
+        let url = TypeHint::Named(NamedType::Url);
+        assert!(url.matches("postgres://user@localhost:5432/app"));
+        assert!(!url.matches("localhost:5432"));
+        assert!(!url.matches("://localhost"));
+
+        assert!(TypeHint::Named(NamedType::Int).matches("-42"));
+        assert!(!TypeHint::Named(NamedType::Int).matches("4.2"));
+        assert!(TypeHint::Named(NamedType::Bool).matches("TRUE"));
+        assert!(!TypeHint::Named(NamedType::Bool).matches("maybe"));
+        assert!(!TypeHint::Enum(vec!["info".to_string()]).matches("debug"));
+    }
+
+    #[test]
+    fn test_check_vars_should_report_missing_undeclared_and_invalid() {
+        let vars = BTreeMap::from([("PORT", "abc"), ("EXTRA", "1")]);
+        let types = BTreeMap::from([("PORT".to_string(), TypeHint::Named(NamedType::Int))]);
+
+        assert_eq!(
+            check_vars(&vars, &["PORT", "DATABASE_URL"], &types),
+            vec![
+                "DATABASE_URL is missing".to_string(),
+                "EXTRA is not declared in .env.example".to_string(),
+                "PORT is not an int".to_string(),
+            ]
+        );
+    }
+}
@@ -1,17 +1,21 @@
 use anyhow::{anyhow, Result};
 
 use crate::nvy_config::{does_config_exist, load_config, CONFIG_FILE_NAME};
+use crate::workspace::render_members;
 
 pub fn run_config() -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
     let config = load_config()?;
     println!("{}", config);
+    if !config.members.is_empty() {
+        print!("{}", render_members(&config, |member| member.to_string())?);
+    }
 
     Ok(())
 }
\ No newline at end of file
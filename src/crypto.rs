@@ -0,0 +1,237 @@
+use age::armor::{ArmoredReader, ArmoredWriter, Format as ArmorFormat};
+use age::secrecy::{ExposeSecret, SecretString};
+use age::{x25519, Decryptor, Encryptor, Identity, Recipient};
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::{env, fs};
+
+use crate::nvy_config::{
+    create_nvy_dir, does_config_exist, get_profile_paths, load_config, lock_config, save_config, CONFIG_FILE_NAME, NVY_DIR,
+};
+use crate::{success, warn};
+
+/// Encrypted profile files are age files with this extension
+pub const ENCRYPTED_EXTENSION: &str = "age";
+
+/// The age identity used to encrypt and decrypt profiles, unless `NVY_IDENTITY` is set
+pub const DEFAULT_IDENTITY_PATH: &str = ".nvy/identity.txt";
+
+const IDENTITY_VAR: &str = "NVY_IDENTITY";
+const PASSPHRASE_VAR: &str = "NVY_PASSPHRASE";
+
+#[derive(Default)]
+pub struct EncryptOptions {
+    /// Encrypt with the passphrase in `NVY_PASSPHRASE` instead of the identity
+    pub passphrase: bool,
+    /// Other age public keys that should also be able to decrypt the profile
+    pub recipients: Vec<String>,
+}
+
+/// Encrypt the files of a profile into `<path>.age` files, and point the profile at them
+pub fn run_encrypt(profile: &String, options: &EncryptOptions) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let _lock = lock_config()?;
+    let mut config = load_config()?;
+    let paths = get_profile_paths(&config, profile)?;
+    let encryptor = || new_encryptor(options);
+
+    let mut encrypted_paths = Vec::new();
+    for path in &paths {
+        if is_encrypted(path) {
+            warn!("{} is already encrypted.", path);
+            continue;
+        }
+
+        let plaintext = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+        let encrypted_path = format!("{}.{}", path, ENCRYPTED_EXTENSION);
+        fs::write(&encrypted_path, encrypt(encryptor()?, &plaintext)?)?;
+        encrypted_paths.push((path.clone(), encrypted_path));
+    }
+
+    if encrypted_paths.is_empty() {
+        return Ok(());
+    }
+
+    for entry in config.profiles.get_mut(profile).into_iter().flatten() {
+        if let Some((_, encrypted_path)) = encrypted_paths.iter().find(|(path, _)| *path == entry.path) {
+            entry.path = encrypted_path.clone();
+        }
+    }
+    save_config(&config)?;
+
+    let new_paths: Vec<&str> = encrypted_paths.iter().map(|(_, encrypted)| encrypted.as_str()).collect();
+    success!("Encrypted profile {} to {}", profile, new_paths.join(", "));
+    for (path, _) in &encrypted_paths {
+        warn!("{} still contains the plaintext, delete it or keep it out of version control.", path);
+    }
+
+    Ok(())
+}
+
+pub fn is_encrypted(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext == ENCRYPTED_EXTENSION)
+}
+
+/// Read a profile file, decrypting it in memory if it is encrypted
+pub fn read_profile_file(path: &str) -> Result<String> {
+    if !is_encrypted(path) {
+        return Ok(fs::read_to_string(path)?);
+    }
+
+    let ciphertext = fs::read(path)?;
+    let decryptor = Decryptor::new_buffered(ArmoredReader::new(ciphertext.as_slice()))
+        .map_err(|e| anyhow!("Failed to decrypt {}: {}", path, e))?;
+
+    let identities: Vec<Box<dyn Identity>> = if decryptor.is_scrypt() {
+        let passphrase = env::var(PASSPHRASE_VAR).map_err(|_| {
+            anyhow!("{} is encrypted with a passphrase, set {} to decrypt it.", path, PASSPHRASE_VAR)
+        })?;
+        vec![Box::new(age::scrypt::Identity::new(SecretString::from(passphrase)))]
+    } else {
+        let identity_path = identity_path();
+        if !Path::new(&identity_path).exists() {
+            return Err(anyhow!(
+                "{} is encrypted, but there is no identity at {} to decrypt it with. Set {} to the identity file to use.",
+                path,
+                identity_path,
+                IDENTITY_VAR
+            ));
+        }
+        read_identities(&identity_path)?
+            .into_iter()
+            .map(|identity| Box::new(identity) as Box<dyn Identity>)
+            .collect()
+    };
+
+    let mut plaintext = String::new();
+    decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref()))
+        .map_err(|e| anyhow!("Failed to decrypt {}: {}", path, e))?
+        .read_to_string(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+fn identity_path() -> String {
+    env::var(IDENTITY_VAR).unwrap_or_else(|_| DEFAULT_IDENTITY_PATH.to_string())
+}
+
+fn new_encryptor(options: &EncryptOptions) -> Result<Encryptor> {
+    if options.passphrase {
+        let passphrase = env::var(PASSPHRASE_VAR)
+            .map_err(|_| anyhow!("Set {} to the passphrase to encrypt with.", PASSPHRASE_VAR))?;
+        return Ok(Encryptor::with_user_passphrase(SecretString::from(passphrase)));
+    }
+
+    let mut recipients: Vec<Box<dyn Recipient>> = options
+        .recipients
+        .iter()
+        .map(|recipient| {
+            recipient
+                .parse::<x25519::Recipient>()
+                .map(|recipient| Box::new(recipient) as Box<dyn Recipient>)
+                .map_err(|e| anyhow!("Invalid recipient {}: {}", recipient, e))
+        })
+        .collect::<Result<_>>()?;
+
+    for identity in load_or_create_identity()? {
+        recipients.push(Box::new(identity.to_public()));
+    }
+
+    Ok(Encryptor::with_recipients(recipients.iter().map(|recipient| recipient.as_ref()))?)
+}
+
+fn encrypt(encryptor: Encryptor, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let mut ciphertext = Vec::new();
+    let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(&mut ciphertext, ArmorFormat::AsciiArmor)?)?;
+    writer.write_all(plaintext)?;
+    writer.finish()?.finish()?;
+    Ok(ciphertext)
+}
+
+/// Read the identity file, generating a new one on first use
+fn load_or_create_identity() -> Result<Vec<x25519::Identity>> {
+    let path = identity_path();
+    if Path::new(&path).exists() {
+        return read_identities(&path);
+    }
+
+    let identity = x25519::Identity::generate();
+    if Path::new(&path).starts_with(NVY_DIR) {
+        create_nvy_dir()?;
+    }
+    if let Some(dir) = Path::new(&path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = create_private_file(&path)?;
+    write!(
+        file,
+        "# public key: {}\n{}\n",
+        identity.to_public(),
+        identity.to_string().expose_secret()
+    )?;
+    success!("Created identity {}, keep it out of version control.", path);
+
+    Ok(vec![identity])
+}
+
+fn read_identities(path: &str) -> Result<Vec<x25519::Identity>> {
+    let contents = fs::read_to_string(path)?;
+    let identities = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.parse::<x25519::Identity>()
+                .map_err(|e| anyhow!("Invalid identity in {}: {}", path, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if identities.is_empty() {
+        return Err(anyhow!("{} does not contain an identity.", path));
+    }
+    Ok(identities)
+}
+
+/// Create a file that only its owner can read, from the moment it exists
+#[cfg(unix)]
+fn create_private_file(path: &str) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &str) -> io::Result<File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+
+    #[test]
+    fn test_is_encrypted_should_check_extension() {
+        assert!(is_encrypted(".env.prod.age"));
+        assert!(!is_encrypted(".env.prod"));
+        assert!(!is_encrypted(".env.age.bak"));
+    }
+
+    #[test]
+    fn test_encrypt_should_round_trip_with_identity() {
+        let identity = x25519::Identity::generate();
+        let recipient = identity.to_public();
+        let encryptor = Encryptor::with_recipients(iter::once(&recipient as &dyn Recipient)).unwrap();
+
+        let ciphertext = encrypt(encryptor, b"KEY=value\n").unwrap();
+        assert!(String::from_utf8(ciphertext.clone()).unwrap().starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+        assert_eq!(age::decrypt(&identity, &ciphertext).unwrap(), b"KEY=value\n");
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::nvy_config::{does_config_exist, load_config, Config, CONFIG_FILE_NAME, SECRET_MASK};
+use crate::r#use::export_profiles;
+
+#[derive(Default)]
+pub struct DiffOptions {
+    /// Print the values of the differing variables instead of only their keys
+    pub show_values: bool,
+}
+
+/// The differences between the variables exported by two sets of profiles
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Diff<'a> {
+    pub(crate) only_in_a: Vec<(&'a str, &'a str)>,
+    pub(crate) only_in_b: Vec<(&'a str, &'a str)>,
+    /// Keys in both, with the value from a and then from b
+    pub(crate) changed: Vec<(&'a str, &'a str, &'a str)>,
+}
+
+impl Diff<'_> {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare the variables that `nvy use` would export for two profiles. Either side can be
+/// a group, or several comma-separated profiles and groups.
+pub fn run_diff(a: &str, b: &str, options: &DiffOptions) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    let (a_vars, a_secrets) = resolve_vars(&config, a)?;
+    let (b_vars, b_secrets) = resolve_vars(&config, b)?;
+    let diff = diff_vars(&a_vars, &b_vars);
+
+    if diff.is_empty() {
+        println!("No differences between {} and {}.", a, b);
+        return Ok(());
+    }
+
+    // A value stays masked if it is secret on either side
+    let show = |key: &str, value: &str| {
+        if a_secrets.contains(key) || b_secrets.contains(key) {
+            SECRET_MASK.to_string()
+        } else {
+            value.to_string()
+        }
+    };
+    let entry = |key: &str, value: &str| {
+        if options.show_values {
+            format!("{}={}", key, show(key, value))
+        } else {
+            key.to_string()
+        }
+    };
+
+    if !diff.only_in_a.is_empty() {
+        println!("only in {}:", a);
+        for (key, value) in &diff.only_in_a {
+            println!("  - {}", entry(key, value));
+        }
+    }
+    if !diff.only_in_b.is_empty() {
+        println!("only in {}:", b);
+        for (key, value) in &diff.only_in_b {
+            println!("  - {}", entry(key, value));
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("changed:");
+        for (key, a_value, b_value) in &diff.changed {
+            if options.show_values {
+                println!("  - {}: {} -> {}", key, show(key, a_value), show(key, b_value));
+            } else {
+                println!("  - {}", key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The merged variables of the profiles, along with the keys of the secret ones
+fn resolve_vars(config: &Config, profiles: &str) -> Result<(BTreeMap<String, String>, BTreeSet<String>)> {
+    let profiles: Vec<String> = profiles.split(',').map(|p| p.trim().to_string()).collect();
+    let result = export_profiles(config, &profiles, false)?;
+    let secrets = result.new_vars.iter().filter(|var| var.secret).map(|var| var.key.clone()).collect();
+    let vars = result.new_vars.into_iter().map(|var| (var.key, var.value)).collect();
+    Ok((vars, secrets))
+}
+
+pub(crate) fn diff_vars<'a>(a: &'a BTreeMap<String, String>, b: &'a BTreeMap<String, String>) -> Diff<'a> {
+    let mut diff = Diff::default();
+
+    for (key, a_value) in a {
+        match b.get(key) {
+            None => diff.only_in_a.push((key, a_value)),
+            Some(b_value) if b_value != a_value => diff.changed.push((key, a_value, b_value)),
+            Some(_) => {}
+        }
+    }
+    for (key, b_value) in b {
+        if !a.contains_key(key) {
+            diff.only_in_b.push((key, b_value));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_diff_vars_should_split_added_removed_and_changed() {
+        let a = vars(&[("SAME", "1"), ("CHANGED", "a"), ("ONLY_A", "x")]);
+        let b = vars(&[("SAME", "1"), ("CHANGED", "b"), ("ONLY_B", "y")]);
+
+        assert_eq!(
+            diff_vars(&a, &b),
+            Diff {
+                only_in_a: vec![("ONLY_A", "x")],
+                only_in_b: vec![("ONLY_B", "y")],
+                changed: vec![("CHANGED", "a", "b")],
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_vars_should_be_empty_for_equal_vars() {
+        let a = vars(&[("KEY", "value")]);
+        assert!(diff_vars(&a, &a.clone()).is_empty());
+    }
+}
@@ -0,0 +1,687 @@
+use anyhow::{anyhow, Result};
+use std::path::Path;
+use std::{fmt, fs};
+
+/// How a value was quoted in an env file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quote {
+    None,
+    Single,
+    Double,
+}
+
+/// A `KEY=value` assignment in an env file, which may span several lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    key: String,
+    value: String,
+    line: usize,
+    quote: Quote,
+    /// Everything on the first line before the value, e.g. `export KEY = `
+    prefix: String,
+    /// Everything on the last line after the value, e.g. ` # a comment`
+    suffix: String,
+    raw: String,
+}
+
+impl Variable {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The 1-based line number the assignment starts on
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn quote(&self) -> Quote {
+        self.quote
+    }
+
+    /// Single-quoted values are taken literally and must not be interpolated
+    pub fn is_literal(&self) -> bool {
+        self.quote == Quote::Single
+    }
+
+    /// The inline comment following the value, without its leading `#`
+    pub fn comment(&self) -> Option<&str> {
+        self.suffix.trim_start().strip_prefix('#').map(str::trim)
+    }
+
+    /// Regenerate the source text after the key or value changed, keeping the
+    /// surrounding formatting and, where it can represent the value, the quote style
+    fn render(&mut self) {
+        let ending = line_ending(&self.raw).to_string();
+        let quote = match self.quote {
+            Quote::None if is_plain(&self.value) => Quote::None,
+            Quote::Single if !self.value.contains('\'') => Quote::Single,
+            _ => Quote::Double,
+        };
+
+        let quoted = match quote {
+            Quote::None => self.value.clone(),
+            Quote::Single => format!("'{}'", self.value),
+            Quote::Double => escape_double_quoted(&self.value),
+        };
+
+        self.quote = quote;
+        self.raw = format!("{}{}{}{}", self.prefix, quoted, self.suffix, ending);
+    }
+}
+
+/// A line of an env file that could not be understood
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// 1-based line number
+    pub line: usize,
+    pub reason: String,
+}
+
+/// A single item of an env file, holding the exact text it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvEntry {
+    Blank(String),
+    Comment(String),
+    Var(Variable),
+    /// A line that could not be parsed, kept so that it is written back unchanged
+    Invalid(String),
+}
+
+impl EnvEntry {
+    pub fn raw(&self) -> &str {
+        match self {
+            EnvEntry::Blank(raw) | EnvEntry::Comment(raw) | EnvEntry::Invalid(raw) => raw,
+            EnvEntry::Var(var) => &var.raw,
+        }
+    }
+}
+
+/// An env file that keeps its entries in order, along with comments and blank lines,
+/// so that it can be edited and written back without disturbing the rest of the file.
+///
+/// Supports the common dotenv/docker-compose syntax: an optional `export ` prefix,
+/// single-quoted literals, double-quoted values with escape sequences that may span
+/// multiple lines, and `# comments` after unquoted or quoted values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvFile {
+    entries: Vec<EnvEntry>,
+    issues: Vec<Issue>,
+}
+
+impl EnvFile {
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut file = Self::default();
+        let mut lines = contents.split_inclusive('\n').enumerate();
+
+        while let Some((idx, raw_line)) = lines.next() {
+            let line = raw_line.trim();
+            if line.is_empty() {
+                file.entries.push(EnvEntry::Blank(raw_line.to_string()));
+                continue;
+            }
+            if line.starts_with('#') {
+                file.entries.push(EnvEntry::Comment(raw_line.to_string()));
+                continue;
+            }
+
+            let Some(eq) = raw_line.find('=') else {
+                file.add_issue(idx, "expected KEY=VALUE but found no `=`".to_string(), raw_line.to_string());
+                continue;
+            };
+
+            let key_part = raw_line[..eq].trim();
+            let key = key_part.strip_prefix("export ").map(str::trim_start).unwrap_or(key_part);
+            if key.is_empty() {
+                file.add_issue(idx, "missing variable name before `=`".to_string(), raw_line.to_string());
+                continue;
+            }
+
+            let content = strip_line_ending(raw_line);
+            let rest = &content[eq + 1..];
+            let value_start = eq + 1 + (rest.len() - rest.trim_start().len());
+            let rest = &content[value_start..];
+
+            let mut var = Variable {
+                key: key.to_string(),
+                value: String::new(),
+                line: idx + 1,
+                quote: Quote::None,
+                prefix: raw_line[..value_start].to_string(),
+                suffix: String::new(),
+                raw: raw_line.to_string(),
+            };
+
+            match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let mut lookahead = lines.clone();
+                    match read_quoted_value(&rest[1..], quote, &mut lookahead) {
+                        Some(quoted) => {
+                            lines = lookahead;
+                            var.value = quoted.value;
+                            var.suffix = quoted.suffix;
+                            var.raw.push_str(&quoted.raw);
+                            var.quote = if quote == '\'' { Quote::Single } else { Quote::Double };
+                        }
+                        // An unterminated quote is kept verbatim rather than swallowing the rest of the file
+                        None => {
+                            file.issues.push(Issue {
+                                line: idx + 1,
+                                reason: format!("unterminated {} in value of {}", quote, key),
+                            });
+                            var.value = strip_inline_comment(rest).to_string();
+                            var.suffix = rest[var.value.len()..].to_string();
+                        }
+                    }
+                }
+                _ => {
+                    var.value = strip_inline_comment(rest).to_string();
+                    var.suffix = rest[var.value.len()..].to_string();
+                }
+            }
+
+            if !is_valid_key(key) {
+                file.add_issue(idx, format!("invalid variable name {}", key), var.raw);
+                continue;
+            }
+
+            file.entries.push(EnvEntry::Var(var));
+        }
+
+        file
+    }
+
+    fn add_issue(&mut self, idx: usize, reason: String, raw: String) {
+        self.issues.push(Issue { line: idx + 1, reason });
+        self.entries.push(EnvEntry::Invalid(raw));
+    }
+
+    pub fn entries(&self) -> &[EnvEntry] {
+        &self.entries
+    }
+
+    /// Lines that were skipped or only partially understood while parsing
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// The variables in the order they appear in the file
+    pub fn vars(&self) -> impl Iterator<Item = &Variable> {
+        self.entries.iter().filter_map(|entry| match entry {
+            EnvEntry::Var(var) => Some(var),
+            _ => None,
+        })
+    }
+
+    /// Look up a variable by key. If it is defined more than once, the last one wins.
+    pub fn get(&self, key: &str) -> Option<&Variable> {
+        self.vars().filter(|var| var.key == key).last()
+    }
+
+    /// The keys of the variables annotated with the given comment, either after the value
+    /// (`KEY=value # nvy:secret`) or on the line directly above the variable
+    pub fn annotated_keys(&self, annotation: &str) -> Vec<&str> {
+        let mut keys = Vec::new();
+        let mut previous: Option<&EnvEntry> = None;
+
+        for entry in &self.entries {
+            if let EnvEntry::Var(var) = entry {
+                let above = matches!(
+                    previous,
+                    Some(EnvEntry::Comment(raw)) if raw.trim().strip_prefix('#').map(str::trim) == Some(annotation)
+                );
+                if above || var.comment() == Some(annotation) {
+                    keys.push(var.key.as_str());
+                }
+            }
+            previous = Some(entry);
+        }
+        keys
+    }
+
+    /// Set the value of a variable, updating its last definition in place or
+    /// appending it to the end of the file if it is not defined yet
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if !is_valid_key(key) {
+            return Err(anyhow!("Invalid variable name {}", key));
+        }
+
+        let existing = self.entries.iter_mut().rev().find_map(|entry| match entry {
+            EnvEntry::Var(var) if var.key == key => Some(var),
+            _ => None,
+        });
+
+        if let Some(var) = existing {
+            if var.value != value {
+                var.value = value.to_string();
+                var.render();
+            }
+            return Ok(());
+        }
+
+        if let Some(last) = self.entries.last_mut() {
+            if !last.raw().ends_with('\n') {
+                match last {
+                    EnvEntry::Blank(raw) | EnvEntry::Comment(raw) | EnvEntry::Invalid(raw) => raw.push('\n'),
+                    EnvEntry::Var(var) => var.raw.push('\n'),
+                }
+            }
+        }
+
+        let line = self.to_string().lines().count() + 1;
+        let mut var = Variable {
+            key: key.to_string(),
+            value: value.to_string(),
+            line,
+            quote: Quote::None,
+            prefix: format!("{}=", key),
+            suffix: String::new(),
+            // Only used by render() to pick the line ending
+            raw: "\n".to_string(),
+        };
+        var.render();
+        self.entries.push(EnvEntry::Var(var));
+        Ok(())
+    }
+
+    /// Rename every definition of a variable, keeping its value and formatting, and
+    /// return whether any were found
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<bool> {
+        if !is_valid_key(new) {
+            return Err(anyhow!("Invalid variable name {}", new));
+        }
+
+        let mut found = false;
+        for entry in &mut self.entries {
+            let EnvEntry::Var(var) = entry else { continue };
+            if var.key != old {
+                continue;
+            }
+
+            // The key is the last thing in the prefix before the `=`, and the raw text starts with the prefix
+            let start = var.prefix.rfind(old).expect("the prefix contains the key");
+            let range = start..start + old.len();
+            var.prefix.replace_range(range.clone(), new);
+            var.raw.replace_range(range, new);
+            var.key = new.to_string();
+            found = true;
+        }
+        Ok(found)
+    }
+
+    /// Remove every definition of a variable, returning whether any were found
+    pub fn remove(&mut self, key: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| !matches!(entry, EnvEntry::Var(var) if var.key == key));
+        self.entries.len() != len
+    }
+}
+
+impl fmt::Display for EnvFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            f.write_str(entry.raw())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quote a value for a dotenv file, only when it can't be written as-is.
+/// Values containing `$` are quoted so that they are never interpolated again.
+pub fn quote_value(value: &str) -> String {
+    if is_plain(value) && !value.contains('$') {
+        return value.to_string();
+    }
+
+    if !value.contains('\'') && !value.contains('\n') {
+        return format!("'{}'", value);
+    }
+
+    escape_double_quoted(value)
+}
+
+fn is_plain(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| !c.is_whitespace() && !matches!(c, '#' | '"' | '\'' | '\\' | '`'))
+}
+
+fn escape_double_quoted(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn line_ending(raw: &str) -> &str {
+    if raw.ends_with("\r\n") {
+        "\r\n"
+    } else if raw.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    }
+}
+
+fn strip_line_ending(raw: &str) -> &str {
+    &raw[..raw.len() - line_ending(raw).len()]
+}
+
+struct QuotedValue {
+    value: String,
+    /// Text after the closing quote on its line
+    suffix: String,
+    /// The raw text of any further lines the value spans
+    raw: String,
+}
+
+/// Read a quoted value starting just after its opening quote, pulling in more lines
+/// until the closing quote is found. Returns `None` if the quote is never closed.
+fn read_quoted_value<'a>(
+    first: &str,
+    quote: char,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Option<QuotedValue> {
+    let mut content = first.to_string();
+    let mut raw = String::new();
+    let end = loop {
+        if let Some(end) = find_closing_quote(&content, quote) {
+            break end;
+        }
+        let (_, next) = lines.next()?;
+        raw.push_str(next);
+        content.push('\n');
+        content.push_str(strip_line_ending(next));
+    };
+
+    let suffix = content[end + 1..].to_string();
+    content.truncate(end);
+
+    let value = if quote == '\'' { content } else { unescape_double_quoted(&content) };
+    Some(QuotedValue { value, suffix, raw })
+}
+
+fn find_closing_quote(raw: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in raw.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote == '"' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn unescape_double_quoted(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some('"') => value.push('"'),
+            Some('\\') => value.push('\\'),
+            // Unknown escapes are kept as-is, e.g. `\$` is left for interpolation to handle
+            Some(other) => {
+                value.push('\\');
+                value.push(other);
+            }
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+/// Strip a trailing `# comment` from an unquoted value. A `#` only starts a comment
+/// at the beginning of the value or when preceded by whitespace.
+fn strip_inline_comment(value: &str) -> &str {
+    let mut prev_is_space = true;
+    for (i, c) in value.char_indices() {
+        if c == '#' && prev_is_space {
+            return value[..i].trim_end();
+        }
+        prev_is_space = c.is_whitespace();
+    }
+    value.trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(contents: &str) -> Vec<(usize, String, String)> {
+        EnvFile::parse(contents)
+            .vars()
+            .map(|var| (var.line, var.key.clone(), var.value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_should_strip_export_prefix() {
+        assert_eq!(parsed("export KEY=value"), vec![(1, "KEY".to_string(), "value".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_trim_whitespace_around_equals() {
+        assert_eq!(parsed("  KEY = value  "), vec![(1, "KEY".to_string(), "value".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_strip_inline_comments_from_unquoted_values() {
+        assert_eq!(parsed("KEY=value # a comment"), vec![(1, "KEY".to_string(), "value".to_string())]);
+        assert_eq!(parsed("KEY= # a comment"), vec![(1, "KEY".to_string(), "".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_keep_hash_without_preceding_whitespace() {
+        assert_eq!(parsed("KEY=abc#def"), vec![(1, "KEY".to_string(), "abc#def".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_keep_equals_in_value() {
+        assert_eq!(parsed("KEY=a=b=c"), vec![(1, "KEY".to_string(), "a=b=c".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_keep_hash_inside_quotes() {
+        assert_eq!(
+            parsed("KEY=\"value # not a comment\" # a comment"),
+            vec![(1, "KEY".to_string(), "value # not a comment".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_should_expand_escapes_in_double_quotes() {
+        assert_eq!(
+            parsed(r#"KEY="line1\nline2\t\"quoted\" \\ \$HOME""#),
+            vec![(1, "KEY".to_string(), "line1\nline2\t\"quoted\" \\ \\$HOME".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_should_not_expand_escapes_in_single_quotes() {
+        assert_eq!(parsed(r"KEY='a\nb'"), vec![(1, "KEY".to_string(), r"a\nb".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_read_multiline_double_quoted_values() {
+        let contents = "KEY=\"-----BEGIN KEY-----\nabc\n-----END KEY-----\"\nNEXT=1";
+        assert_eq!(
+            parsed(contents),
+            vec![
+                (1, "KEY".to_string(), "-----BEGIN KEY-----\nabc\n-----END KEY-----".to_string()),
+                (4, "NEXT".to_string(), "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_should_read_multiline_single_quoted_values() {
+        assert_eq!(
+            parsed("KEY='{\n  \"a\": 1\n}'"),
+            vec![(1, "KEY".to_string(), "{\n  \"a\": 1\n}".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_should_keep_unterminated_quote_on_its_own_line() {
+        assert_eq!(
+            parsed("KEY=\"abc\nNEXT=1"),
+            vec![(1, "KEY".to_string(), "\"abc".to_string()), (2, "NEXT".to_string(), "1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_should_skip_comments_and_lines_without_equals() {
+        assert_eq!(parsed("# comment\n\nNOT_A_VAR\nKEY=1"), vec![(4, "KEY".to_string(), "1".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_should_mark_only_single_quoted_values_as_literal() {
+        let file = EnvFile::parse("A='${B}'\nB=\"${C}\"\nC=${D}");
+        let literals: Vec<bool> = file.vars().map(|var| var.is_literal()).collect();
+        assert_eq!(literals, vec![true, false, false]);
+    }
+
+    #[test]
+    fn test_parse_should_report_malformed_lines() {
+        let file = EnvFile::parse("# comment\nNOT_A_VAR\n=value\nBAD-KEY=1\nQUOTE=\"abc\nOK=1");
+        let issues: Vec<(usize, &str)> = file.issues().iter().map(|i| (i.line, i.reason.as_str())).collect();
+        assert_eq!(
+            issues,
+            vec![
+                (2, "expected KEY=VALUE but found no `=`"),
+                (3, "missing variable name before `=`"),
+                (4, "invalid variable name BAD-KEY"),
+                (5, "unterminated \" in value of QUOTE"),
+            ]
+        );
+        assert_eq!(file.vars().count(), 2);
+    }
+
+    #[test]
+    fn test_to_string_should_round_trip_byte_for_byte() {
+        let contents = "# header\r\n\r\nexport A = 1 # one\r\n  B='multi\nline'  # two\nBAD-KEY=x\nNOT_A_VAR\nC=\"unterminated\nD=\"esc\\n\" ";
+        assert_eq!(EnvFile::parse(contents).to_string(), contents);
+    }
+
+    #[test]
+    fn test_get_should_return_last_definition() {
+        let file = EnvFile::parse("A=1\nB=2\nA=3\n");
+        assert_eq!(file.get("A").map(Variable::value), Some("3"));
+        assert_eq!(file.get("B").map(Variable::line), Some(2));
+        assert!(file.get("C").is_none());
+    }
+
+    #[test]
+    fn test_comment_should_return_inline_comment() {
+        let file = EnvFile::parse("A=1 # nvy:secret\nB=\"2\"#note\nC=3\n");
+        assert_eq!(file.get("A").unwrap().comment(), Some("nvy:secret"));
+        assert_eq!(file.get("B").unwrap().comment(), Some("note"));
+        assert_eq!(file.get("C").unwrap().comment(), None);
+    }
+
+    #[test]
+    fn test_annotated_keys_should_find_inline_and_preceding_comments() {
+        let file = EnvFile::parse("A=1 # nvy:secret\n# nvy:secret\nB=2\n\nC=3\n# nvy:secret\n\nD=4\n");
+        assert_eq!(file.annotated_keys("nvy:secret"), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_set_should_keep_formatting_and_quote_style() {
+        let mut file = EnvFile::parse("# db\nexport DB_HOST = 'localhost' # local\r\nDB_PORT=5432\nNAME=\"app\"\n");
+        file.set("DB_HOST", "db.internal").unwrap();
+        file.set("DB_PORT", "6543").unwrap();
+        file.set("NAME", "my \"app\"").unwrap();
+        assert_eq!(
+            file.to_string(),
+            "# db\nexport DB_HOST = 'db.internal' # local\r\nDB_PORT=6543\nNAME=\"my \\\"app\\\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_set_should_change_quote_style_when_value_needs_it() {
+        let mut file = EnvFile::parse("A=plain\nB='single'\n");
+        file.set("A", "has space").unwrap();
+        file.set("B", "it's").unwrap();
+        assert_eq!(file.to_string(), "A=\"has space\"\nB=\"it's\"\n");
+        assert_eq!(EnvFile::parse(&file.to_string()).get("B").unwrap().value(), "it's");
+    }
+
+    #[test]
+    fn test_set_should_append_new_variables() {
+        let mut file = EnvFile::parse("A=1");
+        file.set("B", "two words").unwrap();
+        assert_eq!(file.to_string(), "A=1\nB=\"two words\"\n");
+        assert_eq!(file.get("B").unwrap().line(), 2);
+    }
+
+    #[test]
+    fn test_set_should_reject_invalid_keys() {
+        let mut file = EnvFile::default();
+        assert!(file.set("BAD-KEY", "1").is_err());
+    }
+
+    #[test]
+    fn test_rename_should_keep_value_and_formatting() {
+        let mut file = EnvFile::parse("export port = 80 # web\nOTHER=1\nport=\"a\nb\"\n");
+        assert!(file.rename("port", "PORT").unwrap());
+        assert_eq!(file.to_string(), "export PORT = 80 # web\nOTHER=1\nPORT=\"a\nb\"\n");
+        assert_eq!(file.get("PORT").unwrap().value(), "a\nb");
+        assert!(!file.rename("MISSING", "NEW").unwrap());
+        assert!(file.rename("OTHER", "not-valid").is_err());
+    }
+
+    #[test]
+    fn test_remove_should_drop_every_definition() {
+        let mut file = EnvFile::parse("A=1\n# keep\nB=2\nA=3\n");
+        assert!(file.remove("A"));
+        assert!(!file.remove("C"));
+        assert_eq!(file.to_string(), "# keep\nB=2\n");
+    }
+
+    #[test]
+    fn test_quote_value_should_leave_plain_values_unquoted() {
+        assert_eq!(quote_value("postgres://localhost:5432/app"), "postgres://localhost:5432/app");
+    }
+
+    #[test]
+    fn test_quote_value_should_round_trip_through_parser() {
+        for value in ["hello world", "it's", "a\nb", "{\"a\": \"b\"}", "back\\slash", "$HOME", ""] {
+            let line = format!("KEY={}", quote_value(value));
+            assert_eq!(parsed(&line), vec![(1, "KEY".to_string(), value.to_string())], "{}", line);
+        }
+    }
+}
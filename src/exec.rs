@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Result};
+use std::process::Command;
+
+use crate::nvy_config::{does_config_exist, invocation_dir, load_config, CONFIG_FILE_NAME};
+use crate::r#use::export_profiles;
+
+/// Run a command with the given profile(s) applied to its environment. Neither the target
+/// nor nvy.yaml are modified.
+pub fn run_exec(profiles: &[String], command: &[String]) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("No command provided to run."))?;
+
+    let config = load_config()?;
+    let result = export_profiles(&config, profiles, false)?;
+
+    let mut child = Command::new(program);
+    child
+        .args(args)
+        .current_dir(invocation_dir()?)
+        .envs(result.new_vars.iter().map(|var| (&var.key, &var.value)));
+
+    run_command(child, program)
+}
+
+/// Replace this process with the command, so that its exit code and any signals are
+/// passed through untouched
+#[cfg(unix)]
+fn run_command(mut child: Command, program: &str) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let err = child.exec();
+    Err(anyhow!("Failed to run {}: {}", program, err))
+}
+
+#[cfg(not(unix))]
+fn run_command(mut child: Command, program: &str) -> Result<()> {
+    let status = child
+        .status()
+        .map_err(|err| anyhow!("Failed to run {}: {}", program, err))?;
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::env::{quote_value, EnvFile};
+use crate::nvy_config::Config;
+use crate::r#use::{EnvVar, ExportResult};
+
+/// The formats a file target can be written in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lowercase")]
+pub enum Format {
+    /// `KEY=value` lines
+    Env,
+    Json,
+    Yaml,
+    /// A Kubernetes ConfigMap manifest
+    ConfigMap,
+    /// A Kubernetes Secret manifest
+    Secret,
+    /// A systemd `EnvironmentFile`
+    Systemd,
+    /// A Makefile fragment to `include`
+    Make,
+}
+
+impl Format {
+    /// Guess the format from the target's extension, falling back to `KEY=value` lines
+    pub fn from_target(target: &str) -> Self {
+        match Path::new(target).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml" | "yml") => Format::Yaml,
+            Some("mk") => Format::Make,
+            _ => Format::Env,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// The format to write the target in: the `format` in the config if set, otherwise
+/// guessed from the target's extension
+pub fn target_format(config: &Config) -> Format {
+    config.format.unwrap_or_else(|| Format::from_target(&config.target))
+}
+
+/// Render the exported variables as the contents of a target file
+pub(crate) fn render(format: Format, result: &ExportResult, target: &str) -> Result<String> {
+    match format {
+        Format::Env => render_lines(result, |var| Ok(format!("{}={}", var.key, quote_value(&var.value)))),
+        Format::Systemd => render_lines(result, |var| Ok(format!("{}={}", var.key, systemd_value(&var.value)))),
+        Format::Make => render_lines(result, |var| Ok(format!("export {} := {}", var.key, make_value(var)?))),
+        Format::Json => {
+            let mut content = serde_json::to_string_pretty(&to_map(result))?;
+            content.push('\n');
+            Ok(content)
+        }
+        Format::Yaml => Ok(serde_yaml::to_string(&to_map(result))?),
+        Format::ConfigMap => Ok(serde_yaml::to_string(&Manifest {
+            api_version: "v1",
+            kind: "ConfigMap",
+            metadata: Metadata { name: manifest_name(target) },
+            secret_type: None,
+            data: Some(to_map(result)),
+            string_data: None,
+        })?),
+        Format::Secret => Ok(serde_yaml::to_string(&Manifest {
+            api_version: "v1",
+            kind: "Secret",
+            metadata: Metadata { name: manifest_name(target) },
+            secret_type: Some("Opaque"),
+            data: None,
+            string_data: Some(to_map(result)),
+        })?),
+    }
+}
+
+/// Read the variables back out of a target file, for the formats that can be parsed
+/// without ambiguity
+pub(crate) fn parse(format: Format, content: &str) -> Option<BTreeMap<String, String>> {
+    match format {
+        Format::Env => Some(
+            EnvFile::parse(content)
+                .vars()
+                .map(|var| (var.key().to_string(), var.value().to_string()))
+                .collect(),
+        ),
+        Format::Json | Format::Yaml => serde_yaml::from_str(content).ok(),
+        Format::ConfigMap | Format::Secret => {
+            let manifest: serde_yaml::Value = serde_yaml::from_str(content).ok()?;
+            let data = manifest.get("data").or_else(|| manifest.get("stringData"))?;
+            serde_yaml::from_value(data.clone()).ok()
+        }
+        Format::Systemd | Format::Make => None,
+    }
+}
+
+/// Render one line per variable, grouped under a comment for each profile
+fn render_lines(result: &ExportResult, line: impl Fn(&EnvVar) -> Result<String>) -> Result<String> {
+    let mut content = String::new();
+
+    for profile in &result.profile_order {
+        let mut vars = result.new_vars.iter().filter(|var| &var.source_profile == profile).peekable();
+        if vars.peek().is_some() {
+            content.push_str(&format!("# {}\n", profile));
+            for var in vars {
+                content.push_str(&line(var)?);
+                content.push('\n');
+            }
+            content.push('\n');
+        }
+    }
+
+    content = content.trim_end().to_string();
+    content.push('\n');
+    Ok(content)
+}
+
+fn to_map(result: &ExportResult) -> BTreeMap<&str, &str> {
+    result.new_vars.iter().map(|var| (var.key.as_str(), var.value.as_str())).collect()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    api_version: &'a str,
+    kind: &'a str,
+    metadata: Metadata,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    secret_type: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<BTreeMap<&'a str, &'a str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    string_data: Option<BTreeMap<&'a str, &'a str>>,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    name: String,
+}
+
+/// Name a manifest after the target file, e.g. `k8s/api-config.yaml` becomes `api-config`,
+/// keeping to the characters Kubernetes allows in a name
+fn manifest_name(target: &str) -> String {
+    let stem = Path::new(target)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '-' })
+        .collect();
+    let name = name.trim_matches(|c| c == '-' || c == '.');
+
+    if name.is_empty() {
+        "nvy".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// systemd reads a double-quoted value literally apart from `\` escapes, and keeps any
+/// newlines in it
+fn systemd_value(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(c));
+    if is_plain {
+        return value.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// make expands `$` and treats `#` as the start of a comment, and a variable can't
+/// span lines
+fn make_value(var: &EnvVar) -> Result<String> {
+    if var.value.contains('\n') {
+        return Err(anyhow!(
+            "Variable {} contains a newline, which can't be written to a Makefile.",
+            var.key
+        ));
+    }
+    Ok(var.value.replace('$', "$$").replace('#', "\\#"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_target_should_use_extension() {
+        assert_eq!(Format::from_target("config/env.json"), Format::Json);
+        assert_eq!(Format::from_target("values.yml"), Format::Yaml);
+        assert_eq!(Format::from_target("env.mk"), Format::Make);
+        assert_eq!(Format::from_target(".env.nvy"), Format::Env);
+    }
+
+    #[test]
+    fn test_manifest_name_should_follow_file_name() {
+        assert_eq!(manifest_name("k8s/API_Config.yaml"), "api-config");
+        assert_eq!(manifest_name("_.yaml"), "nvy");
+    }
+
+    #[test]
+    fn test_systemd_value_should_quote_special_characters() {
+        assert_eq!(systemd_value("postgres://db:5432/app"), "postgres://db:5432/app");
+        assert_eq!(systemd_value(""), "\"\"");
+        assert_eq!(systemd_value("a \"b\" $c\nd"), "\"a \\\"b\\\" \\$c\nd\"");
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+
+use crate::allow::is_config_allowed;
+use crate::nvy_config::{config_path, does_config_exist, load_config};
+use crate::r#use::{export_profiles, to_shell_commands};
+use crate::shell::Shell;
+use crate::warn;
+
+/// The directory whose profiles are currently applied by the hook
+const HOOK_DIR_VAR: &str = "NVY_DIR";
+
+/// A JSON object of the variables the hook exported and the values they had before, with
+/// null for a variable that wasn't set, so they can be restored on leaving
+const HOOK_PREVIOUS_VAR: &str = "NVY_PREVIOUS";
+
+/// The shells that `nvy hook` can print a hook for
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl HookShell {
+    fn dialect(&self) -> Shell {
+        match self {
+            HookShell::Bash | HookShell::Zsh => Shell::Posix,
+            HookShell::Fish => Shell::Fish,
+        }
+    }
+}
+
+/// Print a hook that applies a project's current profiles whenever the shell enters its
+/// directory, and unsets them again when leaving
+pub fn run_hook(shell: HookShell) -> Result<()> {
+    let exe = env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "nvy".to_string());
+    let nvy = shell.dialect().quote(&exe);
+
+    let hook = match shell {
+        HookShell::Bash => format!(
+            r#"_nvy_hook() {{
+  local previous_exit_status=$?
+  if [[ "${{_NVY_PWD:-}}" != "$PWD" ]]; then
+    _NVY_PWD="$PWD"
+    eval "$({nvy} hook-env bash)"
+  fi
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_nvy_hook;"* ]]; then
+  PROMPT_COMMAND="_nvy_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#
+        ),
+        HookShell::Zsh => format!(
+            r#"_nvy_hook() {{
+  eval "$({nvy} hook-env zsh)"
+}}
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_nvy_hook]}} )); then
+  chpwd_functions=(_nvy_hook $chpwd_functions)
+fi
+_nvy_hook
+"#
+        ),
+        HookShell::Fish => format!(
+            r#"function __nvy_hook --on-variable PWD
+    {nvy} hook-env fish | source
+end
+__nvy_hook
+"#
+        ),
+    };
+
+    print!("{}", hook);
+    Ok(())
+}
+
+/// Print the commands the hook evaluates after a directory change
+pub fn run_hook_env(shell: HookShell) -> Result<()> {
+    let dialect = shell.dialect();
+    let active_dir = env::var(HOOK_DIR_VAR).ok();
+    let project_dir = if does_config_exist() {
+        Some(env::current_dir()?.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+
+    // Like direnv, only apply configs that the user has allowed, as entering a cloned
+    // project must not be enough to set e.g. PATH in their shell
+    let project_dir = match project_dir {
+        Some(dir) if !is_config_allowed()? => {
+            warn!("{} in {} is not allowed, run `nvy allow` to apply its profiles.", config_path(), dir);
+            None
+        }
+        project_dir => project_dir,
+    };
+
+    if active_dir == project_dir {
+        return Ok(());
+    }
+
+    // The values the variables had before the hook exported them, or none if they were unset
+    let previous: BTreeMap<String, Option<String>> = env::var(HOOK_PREVIOUS_VAR)
+        .ok()
+        .and_then(|previous| serde_json::from_str(&previous).ok())
+        .unwrap_or_default();
+
+    let mut commands = String::new();
+    match project_dir {
+        Some(dir) => {
+            let config = load_config()?;
+            let result = export_profiles(&config, &config.current_profiles, false)?;
+
+            let mut saved = BTreeMap::new();
+            for var in &result.new_vars {
+                let value = match previous.get(&var.key) {
+                    Some(value) => value.clone(),
+                    None => env::var(&var.key).ok(),
+                };
+                saved.insert(var.key.clone(), value);
+            }
+
+            let left: BTreeMap<String, Option<String>> = previous
+                .into_iter()
+                .filter(|(key, _)| !saved.contains_key(key))
+                .collect();
+            commands.push_str(&restore_commands(dialect, &left));
+            commands.push_str(&to_shell_commands(dialect, &BTreeSet::new(), &result, false));
+            commands.push_str(&dialect.export(HOOK_DIR_VAR, &dir));
+            commands.push('\n');
+            commands.push_str(&dialect.export(HOOK_PREVIOUS_VAR, &serde_json::to_string(&saved)?));
+            commands.push('\n');
+        }
+        None => {
+            commands.push_str(&restore_commands(dialect, &previous));
+            for key in [HOOK_DIR_VAR, HOOK_PREVIOUS_VAR] {
+                commands.push_str(&dialect.unset(key));
+                commands.push('\n');
+            }
+        }
+    }
+
+    print!("{}", commands);
+    Ok(())
+}
+
+/// Put variables back the way they were before the hook exported them
+fn restore_commands(dialect: Shell, previous: &BTreeMap<String, Option<String>>) -> String {
+    let mut commands = String::new();
+    for (key, value) in previous {
+        match value {
+            Some(value) => commands.push_str(&dialect.export(key, value)),
+            None => commands.push_str(&dialect.unset(key)),
+        }
+        commands.push('\n');
+    }
+    commands
+}
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Result};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::env::{is_valid_key, quote_value};
+use crate::nvy_config::{does_config_exist, does_file_exist, load_config, write_atomic, CONFIG_FILE_NAME};
+use crate::profiles::run_profiles_set;
+use crate::{success, warn};
+
+pub struct ImportOptions {
+    /// The docker-compose service to import the environment of
+    pub service: Option<String>,
+    /// Replace the profile, and overwrite its file, if either already exists
+    pub force: bool,
+}
+
+/// Import the variables in a JSON or YAML file, or a docker-compose service's
+/// `environment:`, into `.env.<profile>` and register it as the profile
+pub fn run_import(source: &String, profile: &String, options: &ImportOptions) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    if config.profiles.contains_key(profile) && !options.force {
+        return Err(anyhow!("Profile {} already exists, pass --force to replace it.", profile));
+    }
+
+    let path = format!(".env.{}", profile);
+    if does_file_exist(&path) && !options.force {
+        return Err(anyhow!("File {} already exists, pass --force to overwrite it.", path));
+    }
+
+    let content = fs::read_to_string(source).map_err(|e| anyhow!("Failed to read {}: {}", source, e))?;
+    // JSON is a subset of YAML, so one parser covers both
+    let value: Value = serde_yaml::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", source, e))?;
+
+    let vars = match value.get("services") {
+        Some(services) => compose_vars(source, services, options.service.as_ref())?,
+        None => {
+            if let Some(service) = &options.service {
+                return Err(anyhow!("{} is not a docker-compose file, so it has no service {}.", source, service));
+            }
+            let mut vars = Vec::new();
+            match value {
+                Value::Mapping(_) => flatten(&value, "", &mut vars),
+                _ => return Err(anyhow!("{} must contain a mapping of variables.", source)),
+            }
+            vars
+        }
+    };
+
+    // Check every key before writing anything, so that no value is silently lost
+    let mut names: BTreeMap<String, &str> = BTreeMap::new();
+    let mut content = String::new();
+    for (name, value) in &vars {
+        if name.is_empty() {
+            return Err(anyhow!("{} contains a variable with an empty name, which can't be imported.", source));
+        }
+        let key = to_key(name);
+        if let Some(other) = names.insert(key.clone(), name) {
+            return Err(anyhow!(
+                "{} and {} in {} would both be imported as {}, rename one of them.",
+                other,
+                name,
+                source,
+                key
+            ));
+        }
+        content.push_str(&format!("{}={}\n", key, literal_value(value)));
+    }
+    write_atomic(&path, content)?;
+    success!("Imported {} variable(s) from {} into {}", vars.len(), source, path);
+
+    run_profiles_set(profile, &[path], &[], false)
+}
+
+/// Collect the `environment:` of a docker-compose service, which may be either a mapping
+/// or a list of `KEY=VALUE` strings
+fn compose_vars(source: &str, services: &Value, service: Option<&String>) -> Result<Vec<(String, String)>> {
+    let Some(services) = services.as_mapping() else {
+        return Err(anyhow!("services in {} must be a mapping.", source));
+    };
+
+    let (name, environment) = match service {
+        Some(name) => {
+            let environment = services
+                .get(name.as_str())
+                .ok_or_else(|| anyhow!("Service {} does not exist in {}.", name, source))?
+                .get("environment")
+                .ok_or_else(|| anyhow!("Service {} in {} does not define an environment.", name, source))?;
+            (name.clone(), environment)
+        }
+        None => {
+            let candidates: Vec<(String, &Value)> = services
+                .iter()
+                .filter_map(|(name, definition)| Some((scalar_to_string(name)?, definition.get("environment")?)))
+                .collect();
+            match candidates.len() {
+                0 => return Err(anyhow!("No service in {} defines an environment.", source)),
+                1 => candidates.into_iter().next().unwrap(),
+                _ => {
+                    let names: Vec<&str> = candidates.iter().map(|(name, _)| name.as_str()).collect();
+                    return Err(anyhow!(
+                        "Services {} in {} all define an environment, pick one with --service.",
+                        names.join(", "),
+                        source
+                    ));
+                }
+            }
+        }
+    };
+
+    let mut vars = Vec::new();
+    match environment {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let Some(key) = scalar_to_string(key) else { continue };
+                if value.is_null() {
+                    warn!("Skipping {} in service {}, as its value comes from the host environment.", key, name);
+                } else if let Some(value) = scalar_to_string(value) {
+                    vars.push((key, value));
+                } else {
+                    warn!("Skipping {} in service {}, as it is not a plain value.", key, name);
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items.iter().filter_map(scalar_to_string) {
+                match item.split_once('=') {
+                    Some((key, value)) => vars.push((key.to_string(), value.to_string())),
+                    None => {
+                        warn!("Skipping {} in service {}, as its value comes from the host environment.", item, name);
+                    }
+                }
+            }
+        }
+        _ => return Err(anyhow!("The environment of service {} in {} must be a mapping or a list.", name, source)),
+    }
+    Ok(vars)
+}
+
+/// Flatten nested mappings into variables, joining their keys with `_`
+fn flatten(value: &Value, prefix: &str, vars: &mut Vec<(String, String)>) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping {
+                let Some(key) = scalar_to_string(key) else { continue };
+                let name = if prefix.is_empty() { key } else { format!("{}_{}", prefix, key) };
+                flatten(value, &name, vars);
+            }
+        }
+        Value::Sequence(_) => {
+            warn!("Skipping {}, as lists can't be imported as a variable.", prefix);
+        }
+        Value::Tagged(tagged) => flatten(&tagged.value, prefix, vars),
+        _ => vars.push((prefix.to_string(), scalar_to_string(value).unwrap_or_default())),
+    }
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Null => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Quote an imported value so that it is taken literally, as nothing in it refers to other variables
+fn literal_value(value: &str) -> String {
+    let quoted = quote_value(value);
+    if quoted.starts_with('"') {
+        // Only single quotes are never interpolated, so escape references inside double quotes
+        return quoted.replace('$', "\\$");
+    }
+    quoted
+}
+
+/// Replace any characters that can't appear in a variable name with `_`
+fn to_key(name: &str) -> String {
+    if is_valid_key(name) {
+        return name.to_string();
+    }
+
+    let key: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    warn!("Renamed {} to {}, as it is not a valid variable name.", name, key);
+    key
+}
@@ -4,13 +4,17 @@ use std::collections::BTreeMap;
 use std::io::{self};
 use std::path::PathBuf;
 
-use crate::nvy_config::{does_config_exist, is_target_shell, load_config, save_config, Config, Profile, DEFAULT_TARGET};
+use crate::crypto::ENCRYPTED_EXTENSION;
+use crate::format::Format;
+use crate::nvy_config::{does_config_exist, does_file_exist, is_target_shell, load_config, save_config, Config, Profile, DEFAULT_TARGET, EXAMPLE_FILE_NAME};
 use crate::log::{message, wrap_yellow};
 use crate::{success, warn};
 
 pub fn run_init() -> Result<()> {
     let mut target = String::from(DEFAULT_TARGET);
-    let mut ignore = vec![".env.example".to_string()];
+    let mut format = None;
+    let mut ignore = vec![EXAMPLE_FILE_NAME.to_string()];
+    let mut previous = None;
 
     if does_config_exist() {
         if !prompt_reinit()? {
@@ -21,12 +25,14 @@ pub fn run_init() -> Result<()> {
         let config = load_config()?;
         if !is_target_shell(&config) {
             target = config.target.clone();
+            format = config.format;
             ignore.push(target.clone().to_string());
         }
+        previous = Some(config);
     }
 
     let env_files = discover_env_files(ignore)?;
-    init_config(&target, env_files)?;
+    init_config(&target, format, env_files, previous)?;
     Ok(())
 }
 
@@ -61,8 +67,12 @@ fn discover_env_files(ignore: Vec<String>) -> Result<Vec<PathBuf>> {
         .collect())
 }
 
-/// Map an env file path to its corresponding profile name
+/// Map an env file path to its corresponding profile name. An encrypted file belongs to
+/// the same profile as its plaintext.
 fn get_profile_name(file_name: &str) -> Option<String> {
+    let file_name = file_name
+        .strip_suffix(&format!(".{}", ENCRYPTED_EXTENSION))
+        .unwrap_or(file_name);
     if file_name == ".env" {
         Some("default".to_string())
     } else if let Some(suffix) = file_name.strip_prefix(".env.") {
@@ -76,31 +86,64 @@ fn get_profile_name(file_name: &str) -> Option<String> {
     }
 }
 
-fn init_config(target: &str, env_files: Vec<PathBuf>) -> Result<()> {
-    let mut profiles = BTreeMap::new();
+/// Write a config with a profile for each env file. When reinitializing, the profiles,
+/// groups, secrets, types and members of the previous config are kept, as they can't all be
+/// discovered again, and only the env files that no profile uses yet are added.
+fn init_config(target: &str, format: Option<Format>, env_files: Vec<PathBuf>, previous: Option<Config>) -> Result<()> {
+    let (mut profiles, groups, secrets, types, members) = match previous {
+        Some(previous) => (
+            previous.profiles,
+            previous.groups,
+            previous.secrets,
+            previous.types,
+            previous.members,
+        ),
+        None => (BTreeMap::new(), BTreeMap::new(), Vec::new(), BTreeMap::new(), Vec::new()),
+    };
+
+    profiles.retain(|name, entries| {
+        let missing = entries.iter().find(|entry| !entry.path.is_empty() && !does_file_exist(&entry.path));
+        if let Some(missing) = missing {
+            warn!("Removing profile {}, as its file {} no longer exists.", name, missing.path);
+        }
+        missing.is_none()
+    });
 
-    profiles.insert(
-        "default".to_string(),
+    profiles.entry("default".to_string()).or_insert_with(|| {
         vec![Profile {
             path: ".env".to_string(),
-        }],
-    );
+            extends: Vec::new(),
+            secret: false,
+        }]
+    });
 
     for file in env_files {
         let file_name = file.to_string_lossy();
-        if let Some(profile_name) = get_profile_name(&file_name) {
-            if profile_name != "default" { 
-                profiles.insert(
-                    profile_name.to_string(),
-                    vec![Profile {
-                        path: file_name.into_owned(),
-                    }],
-                );
-            }
+        let in_use = profiles.values().flatten().any(|entry| entry.path == file_name);
+        if let Some(profile_name) = get_profile_name(&file_name).filter(|_| !in_use) {
+            profiles.entry(profile_name).or_insert_with(|| {
+                vec![Profile {
+                    path: file_name.into_owned(),
+                    extends: Vec::new(),
+                    secret: false,
+                }]
+            });
         }
     }
 
-    let config = Config { target: target.to_string(), profiles, current_profiles: vec![] };
+    let config = Config {
+        target: target.to_string(),
+        format,
+        profiles,
+        current_profiles: vec![],
+        current_groups: vec![],
+        last_used: None,
+        source_hashes: BTreeMap::new(),
+        groups,
+        secrets,
+        types,
+        members,
+    };
     let res = save_config(&config);
     match res {
         Ok(()) => {
@@ -124,6 +167,7 @@ mod tests {
         assert_eq!(get_profile_name(".env.local"), Some("local".to_string()));
         assert_eq!(get_profile_name(".env.prod"), Some("prod".to_string()));
         assert_eq!(get_profile_name(".env.staging"), Some("staging".to_string()));
+        assert_eq!(get_profile_name(".env.prod.age"), Some("prod".to_string()));
         assert_eq!(get_profile_name(".env."), None);
         assert_eq!(get_profile_name("env"), None);
         assert_eq!(get_profile_name(".environment"), None);
@@ -132,7 +176,7 @@ mod tests {
     #[test]
     fn test_init_config_empty_dir() -> Result<()> {
         let empty_files = Vec::new();
-        init_config(TARGET_SHELL,empty_files)?;
+        init_config(TARGET_SHELL, None, empty_files, None)?;
 
         let content = fs::read_to_string("nvy.yaml")?;
         assert!(content.contains("default:"));
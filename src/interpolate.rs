@@ -0,0 +1,223 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// A value as read from a profile, before any references in it are expanded
+#[derive(Debug)]
+pub struct RawValue {
+    pub value: String,
+    /// Single-quoted values are taken literally and never expanded
+    pub literal: bool,
+}
+
+/// Expand `${VAR}`, `${VAR:-default}` and `${VAR:?error}` references in every value,
+/// resolving them against the other variables in `vars`. A `\$` produces a literal `$`.
+pub fn interpolate(vars: &BTreeMap<String, RawValue>) -> Result<BTreeMap<String, String>> {
+    let mut interpolator = Interpolator {
+        vars,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+    };
+
+    let mut result = BTreeMap::new();
+    for key in vars.keys() {
+        if let Some(value) = interpolator.resolve(key)? {
+            result.insert(key.clone(), value);
+        }
+    }
+    Ok(result)
+}
+
+struct Interpolator<'a> {
+    vars: &'a BTreeMap<String, RawValue>,
+    resolved: HashMap<String, String>,
+    /// The variables currently being expanded, used to detect cycles
+    stack: Vec<String>,
+}
+
+impl Interpolator<'_> {
+    fn resolve(&mut self, key: &str) -> Result<Option<String>> {
+        if let Some(value) = self.resolved.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        let Some(raw) = self.vars.get(key) else {
+            return Ok(None);
+        };
+        if raw.literal {
+            return Ok(Some(raw.value.clone()));
+        }
+
+        if let Some(pos) = self.stack.iter().position(|k| k == key) {
+            let mut cycle = self.stack[pos..].to_vec();
+            cycle.push(key.to_string());
+            return Err(anyhow!("Variable reference cycle detected: {}", cycle.join(" -> ")));
+        }
+
+        self.stack.push(key.to_string());
+        let value = self.expand(&raw.value, key)?;
+        self.stack.pop();
+
+        self.resolved.insert(key.to_string(), value.clone());
+        Ok(Some(value))
+    }
+
+    fn expand(&mut self, template: &str, owner: &str) -> Result<String> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(pos) = rest.find(['\\', '$']) {
+            expanded.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+
+            if let Some(after) = tail.strip_prefix("\\$") {
+                expanded.push('$');
+                rest = after;
+            } else if let Some(after) = tail.strip_prefix("${") {
+                let end = find_closing_brace(after)
+                    .ok_or_else(|| anyhow!("Unterminated variable reference in {}", owner))?;
+                expanded.push_str(&self.expand_reference(&after[..end], owner)?);
+                rest = &after[end + 1..];
+            } else {
+                expanded.push_str(&tail[..1]);
+                rest = &tail[1..];
+            }
+        }
+
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    fn expand_reference(&mut self, expr: &str, owner: &str) -> Result<String> {
+        let (name, modifier) = match expr.split_once(':') {
+            Some((name, modifier)) => (name, Some(modifier)),
+            None => (expr, None),
+        };
+
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!("Invalid variable reference ${{{}}} in {}", expr, owner));
+        }
+
+        let value = self.resolve(name)?;
+        match modifier {
+            None => value.ok_or_else(|| anyhow!("Variable {} referenced by {} is not defined", name, owner)),
+            Some(modifier) => {
+                let value = value.filter(|v| !v.is_empty());
+                if let Some(default) = modifier.strip_prefix('-') {
+                    match value {
+                        Some(value) => Ok(value),
+                        None => self.expand(default, owner),
+                    }
+                } else if let Some(message) = modifier.strip_prefix('?') {
+                    match value {
+                        Some(value) => Ok(value),
+                        None if message.is_empty() => {
+                            Err(anyhow!("Variable {} is required by {} but is not set", name, owner))
+                        }
+                        None => Err(anyhow!("Variable {} is required by {}: {}", name, owner, message)),
+                    }
+                } else {
+                    Err(anyhow!("Invalid variable reference ${{{}}} in {}", expr, owner))
+                }
+            }
+        }
+    }
+}
+
+/// Find the `}` closing a reference, skipping over any nested `${...}` in a default value
+fn find_closing_brace(value: &str) -> Option<usize> {
+    let mut depth = 1;
+    for (i, c) in value.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(entries: &[(&str, &str)]) -> BTreeMap<String, RawValue> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), RawValue { value: v.to_string(), literal: false }))
+            .collect()
+    }
+
+    #[test]
+    fn test_interpolate_should_expand_references() {
+        let result = interpolate(&vars(&[
+            ("DB_USER", "app"),
+            ("DB_HOST", "localhost"),
+            ("DATABASE_URL", "postgres://${DB_USER}@${DB_HOST}/app"),
+        ]))
+        .unwrap();
+        assert_eq!(result["DATABASE_URL"], "postgres://app@localhost/app");
+    }
+
+    #[test]
+    fn test_interpolate_should_expand_chained_references() {
+        let result = interpolate(&vars(&[("A", "${B}-a"), ("B", "${C}-b"), ("C", "c")])).unwrap();
+        assert_eq!(result["A"], "c-b-a");
+    }
+
+    #[test]
+    fn test_interpolate_should_use_default_when_unset_or_empty() {
+        let result = interpolate(&vars(&[
+            ("EMPTY", ""),
+            ("A", "${MISSING:-fallback}"),
+            ("B", "${EMPTY:-fallback}"),
+            ("C", "${MISSING:-${EMPTY:-nested}}"),
+        ]))
+        .unwrap();
+        assert_eq!(result["A"], "fallback");
+        assert_eq!(result["B"], "fallback");
+        assert_eq!(result["C"], "nested");
+    }
+
+    #[test]
+    fn test_interpolate_should_fail_with_required_message() {
+        let err = interpolate(&vars(&[("A", "${MISSING:?must be set}")])).unwrap_err();
+        assert_eq!(err.to_string(), "Variable MISSING is required by A: must be set");
+    }
+
+    #[test]
+    fn test_interpolate_should_fail_on_undefined_reference() {
+        let err = interpolate(&vars(&[("A", "${MISSING}")])).unwrap_err();
+        assert_eq!(err.to_string(), "Variable MISSING referenced by A is not defined");
+    }
+
+    #[test]
+    fn test_interpolate_should_fail_on_cycle() {
+        let err = interpolate(&vars(&[("A", "${B}"), ("B", "${C}"), ("C", "${A}")])).unwrap_err();
+        assert_eq!(err.to_string(), "Variable reference cycle detected: A -> B -> C -> A");
+    }
+
+    #[test]
+    fn test_interpolate_should_leave_bare_dollars_and_escapes() {
+        let result = interpolate(&vars(&[("A", "pa$$word \\${NOT_A_REF}")])).unwrap();
+        assert_eq!(result["A"], "pa$$word ${NOT_A_REF}");
+    }
+
+    #[test]
+    fn test_interpolate_should_not_expand_literal_values() {
+        let mut raw = vars(&[("B", "${A}")]);
+        raw.insert("A".to_string(), RawValue { value: "${NOPE}".to_string(), literal: true });
+        let result = interpolate(&raw).unwrap();
+        assert_eq!(result["A"], "${NOPE}");
+        assert_eq!(result["B"], "${NOPE}");
+    }
+
+    #[test]
+    fn test_interpolate_should_fail_on_unterminated_reference() {
+        let err = interpolate(&vars(&[("A", "${B")])).unwrap_err();
+        assert_eq!(err.to_string(), "Unterminated variable reference in A");
+    }
+}
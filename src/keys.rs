@@ -0,0 +1,191 @@
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io;
+
+use crate::allow::keep_allowed;
+use crate::crypto::is_encrypted;
+use crate::env::{is_valid_key, EnvFile};
+use crate::log::{message, wrap_yellow};
+use crate::nvy_config::{config_path, does_config_exist, expand_groups, get_profile_paths, load_config, lock_config, save_config, Config, CONFIG_FILE_NAME};
+use crate::{success, warn};
+
+#[derive(Default)]
+pub struct KeysOptions {
+    /// The profiles or groups to change. Every profile is changed if empty.
+    pub profiles: Vec<String>,
+    /// Apply the changes without asking for confirmation
+    pub yes: bool,
+}
+
+/// A profile file with a pending change
+struct Edit {
+    path: String,
+    file: EnvFile,
+    /// The 1-based lines of the definitions being changed
+    lines: Vec<usize>,
+}
+
+/// Rename a variable in the files of every (or the selected) profile
+pub fn run_keys_rename(old: &str, new: &str, options: &KeysOptions) -> Result<()> {
+    if !is_valid_key(new) {
+        return Err(anyhow!("Invalid variable name {}", new));
+    }
+
+    let (_lock, mut config) = lock_existing_config()?;
+    let mut edits = Vec::new();
+    for (path, mut file) in read_selected_files(&config, &options.profiles)? {
+        let lines = definition_lines(&file, old);
+        if lines.is_empty() {
+            continue;
+        }
+        if file.get(new).is_some() {
+            return Err(anyhow!("{} already defines {}, so {} can't be renamed to it.", path, new, old));
+        }
+
+        file.rename(old, new)?;
+        edits.push(Edit { path, file, lines });
+    }
+
+    // The config refers to variables by name too, but only for every profile at once
+    let mut config_changed = false;
+    if options.profiles.is_empty() {
+        for secret in config.secrets.iter_mut().filter(|secret| *secret == old) {
+            *secret = new.to_string();
+            config_changed = true;
+        }
+        if let Some(hint) = config.types.remove(old) {
+            config.types.insert(new.to_string(), hint);
+            config_changed = true;
+        }
+    }
+
+    if edits.is_empty() && !config_changed {
+        warn!("Variable {} is not defined in any of the selected profiles.", old);
+        return Ok(());
+    }
+
+    println!("Renaming {} to {}:", old, new);
+    print_preview(&edits, config_changed);
+    if !options.yes && !prompt_apply()? {
+        warn!("No changes were made.");
+        return Ok(());
+    }
+
+    apply(&edits, config_changed.then_some(&config))?;
+    success!("Renamed {} to {} in {} file(s)", old, new, edits.len());
+    Ok(())
+}
+
+/// Remove a variable from the files of every (or the selected) profile
+pub fn run_keys_remove(key: &str, options: &KeysOptions) -> Result<()> {
+    let (_lock, mut config) = lock_existing_config()?;
+    let mut edits = Vec::new();
+    for (path, mut file) in read_selected_files(&config, &options.profiles)? {
+        let lines = definition_lines(&file, key);
+        if file.remove(key) {
+            edits.push(Edit { path, file, lines });
+        }
+    }
+
+    let mut config_changed = false;
+    if options.profiles.is_empty() {
+        let secrets = config.secrets.len();
+        config.secrets.retain(|secret| secret != key);
+        let removed_secret = config.secrets.len() != secrets;
+        let removed_type = config.types.remove(key).is_some();
+        config_changed = removed_secret || removed_type;
+    }
+
+    if edits.is_empty() && !config_changed {
+        warn!("Variable {} is not defined in any of the selected profiles.", key);
+        return Ok(());
+    }
+
+    println!("Removing {}:", key);
+    print_preview(&edits, config_changed);
+    if !options.yes && !prompt_apply()? {
+        warn!("No changes were made.");
+        return Ok(());
+    }
+
+    apply(&edits, config_changed.then_some(&config))?;
+    success!("Removed {} from {} file(s)", key, edits.len());
+    Ok(())
+}
+
+/// Take the config lock and load the config, which stays locked until the changes are applied
+fn lock_existing_config() -> Result<(File, Config)> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+    let lock = lock_config()?;
+    Ok((lock, load_config()?))
+}
+
+/// Read each file of the selected profiles once, even if several profiles share it
+fn read_selected_files(config: &Config, profiles: &[String]) -> Result<Vec<(String, EnvFile)>> {
+    let profiles = if profiles.is_empty() {
+        config.profiles.keys().cloned().collect()
+    } else {
+        expand_groups(config, profiles)?.0
+    };
+
+    let mut paths: Vec<String> = Vec::new();
+    for profile in &profiles {
+        for path in get_profile_paths(config, profile)? {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for path in paths {
+        if is_encrypted(&path) {
+            warn!("Skipping {}, as it is encrypted.", path);
+            continue;
+        }
+        let file = EnvFile::read(&path).map_err(|e| anyhow!("Failed to read {}: {}", path, e))?;
+        files.push((path, file));
+    }
+    Ok(files)
+}
+
+fn definition_lines(file: &EnvFile, key: &str) -> Vec<usize> {
+    file.vars().filter(|var| var.key() == key).map(|var| var.line()).collect()
+}
+
+fn print_preview(edits: &[Edit], config_changed: bool) {
+    for edit in edits {
+        let lines: Vec<String> = edit.lines.iter().map(|line| line.to_string()).collect();
+        println!("  - {} (line {})", edit.path, lines.join(", "));
+    }
+    if config_changed {
+        println!("  - {}", config_path());
+    }
+}
+
+fn prompt_apply() -> Result<bool> {
+    message(vec![&wrap_yellow("Apply these changes? [y/N]")]);
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim().to_lowercase();
+
+    Ok(input == "y" || input == "yes")
+}
+
+fn apply(edits: &[Edit], config: Option<&Config>) -> Result<()> {
+    keep_allowed(|| {
+        for edit in edits {
+            edit.file.write(&edit.path)?;
+        }
+        if let Some(config) = config {
+            save_config(config)?;
+        }
+        Ok(())
+    })
+}
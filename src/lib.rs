@@ -1,7 +1,24 @@
+pub mod allow;
+pub mod backup;
+pub mod check;
 pub mod config;
+pub mod crypto;
+pub mod diff;
+pub mod env;
+pub mod exec;
+pub mod format;
+pub mod hook;
+pub mod import;
 pub mod init;
+pub mod interpolate;
+pub mod keys;
 pub mod log;
 pub mod nvy_config;
 pub mod profiles;
+pub mod shell;
+pub mod show;
+pub mod status;
 pub mod target;
-pub mod r#use;
\ No newline at end of file
+pub mod r#use;
+pub mod vars;
+pub mod workspace;
\ No newline at end of file
@@ -7,16 +7,16 @@ pub fn wrap_yellow(message: &str) -> String {
 
 #[macro_export]
 macro_rules! __log_internal {
-    ($predicate:expr, $color:expr, $template:expr) => {
-        println!("{}{}{}\t{}", 
+    ($print:ident, $predicate:expr, $color:expr, $template:expr) => {
+        $print!("{}{}{}\t{}", 
             $color,
             $predicate,
             inline_colorization::color_reset,
             $template
         );
     };
-    ($predicate:expr, $color:expr, $template:expr, $($arg:tt)*) => {
-        println!(
+    ($print:ident, $predicate:expr, $color:expr, $template:expr, $($arg:tt)*) => {
+        $print!(
             "{}{}{}\t{}", 
             $color,
             $predicate,
@@ -29,36 +29,36 @@ macro_rules! __log_internal {
 #[macro_export]
 macro_rules! success {
     ($template:expr) => {
-        crate::__log_internal!("Success", inline_colorization::color_green, $template);
+        $crate::__log_internal!(println, "Success", inline_colorization::color_green, $template);
     };
     ($template:expr, $($arg:tt)*) => {
-        crate::__log_internal!("Success", inline_colorization::color_green, $template, $($arg)*);
+        $crate::__log_internal!(println, "Success", inline_colorization::color_green, $template, $($arg)*);
     };
 }
 
 #[macro_export]
 macro_rules! warn {
     ($template:expr) => {
-        crate::__log_internal!("Warning", inline_colorization::color_yellow, $template);
+        $crate::__log_internal!(eprintln, "Warning", inline_colorization::color_yellow, $template);
     };
     ($template:expr, $($arg:tt)*) => {
-        crate::__log_internal!("Warning", inline_colorization::color_yellow, $template, $($arg)*);
+        $crate::__log_internal!(eprintln, "Warning", inline_colorization::color_yellow, $template, $($arg)*);
     };
 }
 
 #[macro_export]
 macro_rules! error {
     ($template:expr) => {
-        crate::__log_internal!("Error", inline_colorization::color_red, $template);
+        $crate::__log_internal!(eprintln, "Error", inline_colorization::color_red, $template);
     };
     ($template:expr, $($arg:tt)*) => {
-        crate::__log_internal!("Error", inline_colorization::color_red, $template, $($arg)*);
+        $crate::__log_internal!(eprintln, "Error", inline_colorization::color_red, $template, $($arg)*);
     };
 }
 
 pub fn message(messages: Vec<&str>) {
     for message in messages {
-        print!("{message}\n");
+        println!("{message}");
     }
     let _ = io::stdout().flush();
 }
\ No newline at end of file
@@ -1,16 +1,33 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 
+use nvy::allow::run_allow;
+use nvy::backup::run_restore;
+use nvy::check::run_check;
 use nvy::config::run_config;
-use nvy::nvy_config::TARGET_SHELL;
+use nvy::crypto::{run_encrypt, EncryptOptions};
+use nvy::diff::{run_diff, DiffOptions};
+use nvy::exec::run_exec;
+use nvy::format::Format;
+use nvy::hook::{run_hook, run_hook_env, HookShell};
+use nvy::nvy_config::{from_invocation_dir, locate_config, TARGET_SHELL};
+use nvy::shell::Shell;
+use nvy::import::{run_import, ImportOptions};
 use nvy::init::run_init;
+use nvy::keys::{run_keys_remove, run_keys_rename, KeysOptions};
 use nvy::profiles::{run_profiles, run_profiles_remove, run_profiles_set};
+use nvy::show::run_show;
+use nvy::status::run_status;
 use nvy::target::{run_target, run_target_set};
-use nvy::r#use::run_use;
+use nvy::r#use::{run_use, UseOptions};
+use nvy::vars::{run_get, run_set, run_unset};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// The nvy configuration file to use. Defaults to the nearest nvy.yaml in the current directory or its parents.
+    #[arg(long, global = true, env = "NVY_CONFIG")]
+    config: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -27,6 +44,93 @@ enum Commands {
         #[arg(num_args = 1..)] 
         #[arg(default_values_t = vec!["default".to_string()])]
         profiles: Vec<String>,
+        /// Fail instead of warning when a line in a profile is malformed
+        #[arg(long)]
+        strict: bool,
+        /// Print the changes to the target instead of writing it, without changing the profiles in use
+        #[arg(long)]
+        dry_run: bool,
+        /// Use the profile(s) in every member of the workspace
+        #[arg(long, conflicts_with = "member")]
+        all: bool,
+        /// Use the profile(s) in the given members of the workspace, separated by commas
+        #[arg(long, value_delimiter = ',')]
+        member: Vec<String>,
+    },
+    /// Restore the target file from a backup taken before `nvy use` overwrote it
+    Restore {
+        /// Which backup to restore, counting back from the most recent one
+        #[arg(default_value_t = 1)]
+        n: usize,
+        /// List the backups of the target instead of restoring one
+        #[arg(long)]
+        list: bool,
+    },
+    /// Run a command with the specified profile(s) applied, without modifying the target
+    #[clap(alias = "x")]
+    Exec {
+        /// The profiles to apply. If overlapping environment variables are defined, the last one wins.
+        #[arg(required = true, num_args = 1..)]
+        profiles: Vec<String>,
+        /// The command to run, and its arguments, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Compare the environment variables of two profiles or groups
+    #[clap(alias = "d")]
+    Diff {
+        /// A profile or group, or several comma-separated ones
+        a: String,
+        /// The profile(s) or group(s) to compare against
+        b: String,
+        /// Print the values of the differing variables, which are hidden by default
+        #[arg(long)]
+        show_values: bool,
+    },
+    /// Print the resolved environment variables of the specified profile(s) and where each came from, without modifying the target
+    Show {
+        /// The profiles to show. If overlapping environment variables are defined, the last one wins.
+        #[arg(required = true, num_args = 1..)]
+        profiles: Vec<String>,
+    },
+    /// Check whether the target still matches the current profile(s)
+    #[clap(alias = "s")]
+    Status,
+    /// Check profile(s) against the variables declared in .env.example and the types in the nvy configuration
+    Check {
+        /// The profiles or groups to check. Defaults to every profile.
+        profiles: Vec<String>,
+    },
+    /// Print the value of an environment variable
+    Get {
+        key: String,
+        /// The profile to read from. Defaults to the profile(s) currently in use.
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    /// Set an environment variable in a profile, given as KEY=VALUE
+    Set {
+        assignment: String,
+        #[arg(long)]
+        profile: String,
+        /// Use the current profile(s) again afterwards, to update the target
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Remove an environment variable from a profile
+    Unset {
+        key: String,
+        #[arg(long)]
+        profile: String,
+        /// Use the current profile(s) again afterwards, to update the target
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Rename or remove an environment variable across profiles
+    #[clap(alias = "k")]
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommands,
     },
     /// View the nvy configuration
     #[clap(alias = "c")]
@@ -43,23 +147,95 @@ enum Commands {
         #[command(subcommand)]
         command: Option<ProfileCommands>,
     },
+    /// Import variables from a JSON or YAML file, or a docker-compose service's environment, as a profile
+    Import {
+        /// The file to import from
+        source: String,
+        /// The profile to create. Its variables are written to .env.<profile>.
+        #[arg(long)]
+        profile: String,
+        /// The docker-compose service to import, if the source defines more than one environment
+        #[arg(long)]
+        service: Option<String>,
+        /// Replace the profile, and overwrite .env.<profile>, if either already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Encrypt the file(s) of a profile with age, so that they can be committed
+    Encrypt {
+        profile: String,
+        /// Encrypt with the passphrase in NVY_PASSPHRASE instead of the identity in .nvy/identity.txt (or NVY_IDENTITY)
+        #[arg(long)]
+        passphrase: bool,
+        /// The age public key of someone else who should be able to decrypt the profile
+        #[arg(long = "recipient", conflicts_with = "passphrase")]
+        recipients: Vec<String>,
+    },
+    /// Let the shell hook apply the current profiles of this nvy configuration, until it is changed by something other than nvy
+    Allow,
+    /// Print a shell hook that applies the current profiles whenever you cd into the project
+    Hook {
+        shell: HookShell,
+    },
+    /// Print the commands run by the shell hook after a directory change
+    #[command(name = "hook-env", hide = true)]
+    HookEnv {
+        shell: HookShell,
+    },
 }
 
 #[derive(Subcommand)]
 enum TargetCommands {
     /// Set the target destination for environment variables
     Set {
+        /// A file path, or one of sh, fish, pwsh, nu or csh to output shell commands instead
         #[arg(default_value = TARGET_SHELL)]
         file: String,
+        /// The format to write a file target in. If omitted, it is guessed from the file's extension.
+        #[arg(long)]
+        format: Option<Format>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// Rename an environment variable, keeping its value
+    Rename {
+        old: String,
+        new: String,
+        /// The profile(s) or group(s) to change. Defaults to every profile.
+        #[arg(long = "profile")]
+        profiles: Vec<String>,
+        /// Apply the changes without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Remove an environment variable
+    Remove {
+        key: String,
+        /// The profile(s) or group(s) to change. Defaults to every profile.
+        #[arg(long = "profile")]
+        profiles: Vec<String>,
+        /// Apply the changes without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
 }
 
 #[derive(Subcommand)]
 enum ProfileCommands {
-    /// Set the file path for a given profile. If the profile does not exist, it will be created.
+    /// Set the file path(s) for a given profile. If the profile does not exist, it will be created.
     Set {
         profile: String,
-        file: String,
+        /// The files making up the profile. If overlapping environment variables are defined, the last one wins.
+        #[arg(required = true, num_args = 1..)]
+        files: Vec<String>,
+        /// Profile(s) whose variables are applied before this profile's own
+        #[arg(long)]
+        extends: Vec<String>,
+        /// Mark every variable in the profile as secret, so that its values are never shown
+        #[arg(long)]
+        secret: bool,
     },
     /// Remove the provided profile
     Remove {
@@ -69,21 +245,61 @@ enum ProfileCommands {
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    // Everything runs in the config's directory, except `nvy init` which creates a new one
+    locate_config(cli.config.as_deref(), !matches!(cli.command, Commands::Init))?;
 
     match &cli.command {
         Commands::Init => {
             run_init()?;
         },
-        Commands::Use { profiles } => {
-            run_use(profiles)?;
+        Commands::Use { profiles, strict, dry_run, all, member } => {
+            run_use(profiles, &UseOptions { strict: *strict, dry_run: *dry_run, all: *all, members: member.clone() })?;
+        },
+        Commands::Restore { n, list } => {
+            run_restore(*n, *list)?;
+        },
+        Commands::Exec { profiles, command } => {
+            run_exec(profiles, command)?;
+        },
+        Commands::Diff { a, b, show_values } => {
+            run_diff(a, b, &DiffOptions { show_values: *show_values })?;
+        },
+        Commands::Show { profiles } => {
+            run_show(profiles)?;
+        },
+        Commands::Status => {
+            run_status()?;
+        },
+        Commands::Check { profiles } => {
+            run_check(profiles)?;
+        },
+        Commands::Get { key, profile } => {
+            run_get(key, profile.as_ref())?;
+        },
+        Commands::Set { assignment, profile, apply } => {
+            run_set(assignment, profile, *apply)?;
+        },
+        Commands::Unset { key, profile, apply } => {
+            run_unset(key, profile, *apply)?;
+        },
+        Commands::Keys { command } => {
+            match command {
+                KeysCommands::Rename { old, new, profiles, yes } => {
+                    run_keys_rename(old, new, &KeysOptions { profiles: profiles.clone(), yes: *yes })?;
+                },
+                KeysCommands::Remove { key, profiles, yes } => {
+                    run_keys_remove(key, &KeysOptions { profiles: profiles.clone(), yes: *yes })?;
+                },
+            }
         },
         Commands::Config => {
             run_config()?;
         },
         Commands::Target { command } => {
             match command {
-                Some(TargetCommands::Set { file }) => {
-                    run_target_set(file)?;
+                Some(TargetCommands::Set { file, format }) => {
+                    let file = if Shell::from_target(file).is_some() { file.clone() } else { from_invocation_dir(file)? };
+                    run_target_set(&file, *format)?;
                 },
                 None => {
                     run_target()?;
@@ -95,13 +311,29 @@ fn main() -> Result<()> {
                 None => {
                     run_profiles()?;
                 }
-                Some(ProfileCommands::Set { profile, file }) => {
-                    run_profiles_set(profile, file)?;
+                Some(ProfileCommands::Set { profile, files, extends, secret }) => {
+                    let files = files.iter().map(|file| from_invocation_dir(file)).collect::<Result<Vec<_>>>()?;
+                    run_profiles_set(profile, &files, extends, *secret)?;
                 },
                 Some(ProfileCommands::Remove { profile }) => {
                     run_profiles_remove(profile)?;
                 },
             }
+        },
+        Commands::Import { source, profile, service, force } => {
+            run_import(&from_invocation_dir(source)?, profile, &ImportOptions { service: service.clone(), force: *force })?;
+        },
+        Commands::Encrypt { profile, passphrase, recipients } => {
+            run_encrypt(profile, &EncryptOptions { passphrase: *passphrase, recipients: recipients.clone() })?;
+        },
+        Commands::Allow => {
+            run_allow()?;
+        },
+        Commands::Hook { shell } => {
+            run_hook(*shell)?;
+        },
+        Commands::HookEnv { shell } => {
+            run_hook_env(*shell)?;
         }
     }
 
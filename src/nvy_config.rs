@@ -2,28 +2,93 @@ use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::to_string;
 use std::collections::BTreeMap;
-use std::{fmt, fs};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use std::{env, fmt, fs, process};
+
+use crate::allow::keep_allowed;
+use crate::check::TypeHint;
+use crate::format::Format;
+use crate::shell::Shell;
 
 pub const TARGET_SHELL: &str = "sh";
 
+/// Where nvy keeps its own files for a project, which are never meant to be committed
+pub const NVY_DIR: &str = ".nvy";
+
+/// The file that the advisory lock around changes to the config is taken on
+pub const LOCK_FILE: &str = ".nvy/lock";
+
 /// Set it to this so that there's no chance of accidentally overwriting a user's config
 pub const DEFAULT_TARGET: &str = ".env.nvy";
 
 pub const CONFIG_FILE_NAME: &str = "nvy.yaml";
 
+/// Set once the config has been located, see `locate_config`, and while in a workspace member
+static CONFIG_PATH: RwLock<String> = RwLock::new(String::new());
+static INVOCATION_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Marks a variable in an env file as secret, as a comment after it or on the line above it
+pub const SECRET_ANNOTATION: &str = "nvy:secret";
+
+/// Shown in place of a secret value
+pub const SECRET_MASK: &str = "********";
+
+/// Documents the variables every profile should define, so it is never a profile itself
+pub const EXAMPLE_FILE_NAME: &str = ".env.example";
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+    #[serde(default = "default_target")]
     pub target: String,
 
+    /// The format to write a file target in, instead of guessing it from the extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+
     #[serde(default)]
     pub current_profiles: Vec<String>,
 
-    #[serde(serialize_with = "ordered_map")]
+    /// The groups that were expanded into `current_profiles`, if any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub current_groups: Vec<String>,
+
+    /// When `current_profiles` were last exported, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<f64>,
+
+    /// The sha256 of each file of `current_profiles` when they were last exported
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub source_hashes: BTreeMap<String, String>,
+
+    #[serde(default, serialize_with = "ordered_map")]
     pub profiles: BTreeMap<String, Vec<Profile>>,
+
+    /// Named, ordered lists of profiles that can be used in place of a profile
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>,
+
+    /// Variables whose values are never shown, in any profile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+
+    /// The expected types of variables, checked by `nvy check`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, TypeHint>,
+
+    /// Directories with their own nvy.yaml, which makes this config a workspace of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+}
+
+fn default_target() -> String {
+    DEFAULT_TARGET.to_string()
 }
 
 pub fn is_target_shell(cfg: &Config) -> bool {
-    cfg.target == TARGET_SHELL
+    Shell::from_target(&cfg.target).is_some()
 }
 
 pub fn does_file_exist(path: &str) -> bool {
@@ -31,11 +96,119 @@ pub fn does_file_exist(path: &str) -> bool {
 }
 
 pub fn does_config_exist() -> bool {
-    does_file_exist(CONFIG_FILE_NAME)
+    does_file_exist(&config_path())
+}
+
+/// Find the config to use and move to the directory it is in, so that the paths in it
+/// resolve relative to it. An explicit path comes from `--config` or `NVY_CONFIG`, otherwise
+/// the current directory and its parents are searched, unless a new config is being created.
+pub fn locate_config(path: Option<&str>, existing: bool) -> Result<()> {
+    let invocation_dir = env::current_dir()?;
+    let path = match path {
+        Some(path) => {
+            let path = invocation_dir.join(path);
+            if existing && !path.is_file() {
+                return Err(anyhow!("Config file {} does not exist.", path.display()));
+            }
+            path
+        }
+        None if existing => match find_config(&invocation_dir) {
+            Some(path) => path,
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(anyhow!("Invalid config path {}.", path.display()));
+    };
+    env::set_current_dir(dir).map_err(|e| anyhow!("Failed to change to the directory of {}: {}", path.display(), e))?;
+    set_config_path(name.to_string_lossy().into_owned());
+    let _ = INVOCATION_DIR.set(invocation_dir);
+    Ok(())
+}
+
+/// The nearest nvy.yaml in the directory or one of its parents, like git does for .git
+fn find_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// The path of the config, relative to its directory, which nvy runs in
+pub fn config_path() -> String {
+    let path = CONFIG_PATH.read().unwrap_or_else(|e| e.into_inner());
+    if path.is_empty() {
+        CONFIG_FILE_NAME.to_string()
+    } else {
+        path.clone()
+    }
+}
+
+/// Use a different config from now on, returning the one used until now
+pub(crate) fn set_config_path(path: String) -> String {
+    let mut current = CONFIG_PATH.write().unwrap_or_else(|e| e.into_inner());
+    std::mem::replace(&mut current, path)
+}
+
+/// The directory nvy was invoked from, before moving to the config's directory
+pub fn invocation_dir() -> Result<PathBuf> {
+    match INVOCATION_DIR.get() {
+        Some(dir) => Ok(dir.clone()),
+        None => Ok(env::current_dir()?),
+    }
+}
+
+/// Make a path given on the command line, which is relative to where nvy was invoked,
+/// relative to the config's directory instead
+pub fn from_invocation_dir(path: &str) -> Result<String> {
+    let config_dir = env::current_dir()?;
+    match INVOCATION_DIR.get() {
+        Some(dir) if *dir != config_dir && Path::new(path).is_relative() => {
+            Ok(relative_path(&config_dir, &dir.join(path)).to_string_lossy().into_owned())
+        }
+        _ => Ok(path.to_string()),
+    }
+}
+
+/// The path to an absolute path from an absolute directory, worked out without touching the file system
+fn relative_path(dir: &Path, path: &Path) -> PathBuf {
+    let normalize = |path: &Path| {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    };
+    let (dir, path) = (normalize(dir), normalize(path));
+
+    let common = dir
+        .components()
+        .zip(path.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in dir.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+    relative
 }
 
 pub fn load_config() -> Result<Config, anyhow::Error> {
-    let res = fs::read_to_string(CONFIG_FILE_NAME);
+    load_config_from(Path::new(&config_path()))
+}
+
+pub fn load_config_from(path: &Path) -> Result<Config, anyhow::Error> {
+    let res = fs::read_to_string(path);
     match res {
         Ok(content) => Ok(serde_yaml::from_str(&content)?),
         Err(e) => Err(anyhow::anyhow!(e)),
@@ -44,57 +217,262 @@ pub fn load_config() -> Result<Config, anyhow::Error> {
 
 pub fn save_config(config: &Config) -> Result<()> {
     let yaml = to_string(&config)?;
-    fs::write(CONFIG_FILE_NAME, yaml)?;
+    keep_allowed(|| write_atomic(&config_path(), yaml))
+}
+
+/// Take an exclusive advisory lock for a load-modify-save of the config, which is held until
+/// the returned file is dropped, so that concurrent invocations don't lose each other's changes
+pub fn lock_config() -> Result<File> {
+    create_nvy_dir()?;
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(LOCK_FILE)?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Create the `.nvy` directory, with a `.gitignore` that keeps everything in it out of git
+pub fn create_nvy_dir() -> Result<()> {
+    fs::create_dir_all(NVY_DIR)?;
+    let gitignore = Path::new(NVY_DIR).join(".gitignore");
+    if !gitignore.exists() {
+        fs::write(gitignore, "*\n")?;
+    }
     Ok(())
 }
 
-pub fn get_profile_path(config: &Config, profile: &String) -> Result<String, anyhow::Error> {
-    let path = config.profiles.get(profile);
-    match path {
-        Some(path) => {
-            if path.len() == 0 {
+/// Write a file through a temporary file that is renamed over it, so that a crash
+/// never leaves it half written
+pub fn write_atomic(path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+    // Replace the file a symlink points to, rather than the symlink itself
+    let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid file path {}", path.display()))?;
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id()));
+
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp_path)?;
+        // Restrict the file before anything is written to it, as the contents may be secret
+        if let Ok(metadata) = fs::metadata(&path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+/// Get the files defined directly on a profile, in the order they should be applied
+pub fn get_profile_paths(config: &Config, profile: &String) -> Result<Vec<String>, anyhow::Error> {
+    let paths = config.profiles.get(profile);
+    match paths {
+        Some(paths) => {
+            if paths.is_empty() {
                 return Err(anyhow!("Profile {} does not have any paths defined.", profile));
             }
-            if path.len() > 1 {
-                return Err(anyhow!("Profile {} has more than one path defined.", profile));
-            }
-            let path_str = path[0].path.to_string();
-            if path_str.len() == 0 {
+            if paths.iter().any(|p| p.path.is_empty() && p.extends.is_empty()) {
                 return Err(anyhow!("Profile {} has an empty path defined.", profile));
             }
-            return Ok(path_str);
+            Ok(paths.iter().filter(|p| !p.path.is_empty()).map(|p| p.path.to_string()).collect())
         },
         None => Err(anyhow!("Profile {} does not exist in the {} file.", profile, CONFIG_FILE_NAME)),
     }
 }
 
+/// Get the profiles that a profile extends, in the order they should be applied
+pub fn get_profile_parents(config: &Config, profile: &String) -> Vec<String> {
+    config
+        .profiles
+        .get(profile)
+        .map(|paths| paths.iter().flat_map(|p| p.extends.iter().cloned()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether a profile is marked as secret, so that none of its values are shown
+pub fn is_profile_secret(config: &Config, profile: &str) -> bool {
+    config
+        .profiles
+        .get(profile)
+        .is_some_and(|paths| paths.iter().any(|p| p.secret))
+}
+
+/// Expand any group names into their profiles, returning the profiles to apply
+/// along with the groups that were expanded
+pub fn expand_groups(config: &Config, names: &[String]) -> Result<(Vec<String>, Vec<String>)> {
+    let mut profiles = Vec::new();
+    let mut groups = Vec::new();
+
+    for name in names {
+        let Some(members) = config.groups.get(name) else {
+            profiles.push(name.clone());
+            continue;
+        };
+
+        if config.profiles.contains_key(name) {
+            return Err(anyhow!("{} is defined as both a profile and a group in the {} file.", name, CONFIG_FILE_NAME));
+        }
+        if let Some(member) = members.iter().find(|member| !config.profiles.contains_key(*member)) {
+            return Err(anyhow!("Group {} contains {}, which is not a profile in the {} file.", name, member, CONFIG_FILE_NAME));
+        }
+
+        profiles.extend(members.iter().cloned());
+        groups.push(name.clone());
+    }
+
+    Ok((profiles, groups))
+}
+
+/// The names the current profiles were used by, with the profiles of each group in
+/// `current_groups` collapsed back into the group, so that using them again keeps the groups
+pub fn current_names(config: &Config) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut groups = config.current_groups.iter().peekable();
+    let mut rest = config.current_profiles.as_slice();
+
+    while let Some(profile) = rest.first() {
+        let members = groups.peek().and_then(|group| config.groups.get(*group));
+        match members {
+            Some(members) if !members.is_empty() && rest.starts_with(members) => {
+                names.push(groups.next().unwrap().clone());
+                rest = &rest[members.len()..];
+            }
+            _ => {
+                names.push(profile.clone());
+                rest = &rest[1..];
+            }
+        }
+    }
+    names
+}
+
+/// A file to apply as part of a profile, along with the profile that defines it
+#[derive(Debug, PartialEq)]
+pub struct ProfileLayer {
+    pub profile: String,
+    pub path: String,
+}
+
+/// Resolve a profile into the files to apply, in order: the files of the profiles it
+/// extends come first, so that the profile's own files are applied on top of them.
+pub fn resolve_profile(config: &Config, profile: &String) -> Result<Vec<ProfileLayer>> {
+    let mut layers = Vec::new();
+    resolve_profile_into(config, profile, &mut Vec::new(), &mut layers)?;
+    Ok(layers)
+}
+
+fn resolve_profile_into(
+    config: &Config,
+    profile: &String,
+    stack: &mut Vec<String>,
+    layers: &mut Vec<ProfileLayer>,
+) -> Result<()> {
+    if let Some(pos) = stack.iter().position(|p| p == profile) {
+        let mut cycle = stack[pos..].to_vec();
+        cycle.push(profile.clone());
+        return Err(anyhow!("Profile inheritance cycle detected: {}", cycle.join(" -> ")));
+    }
+
+    let paths = get_profile_paths(config, profile)?;
+
+    stack.push(profile.clone());
+    for parent in get_profile_parents(config, profile) {
+        resolve_profile_into(config, &parent, stack, layers)?;
+    }
+    stack.pop();
+
+    layers.extend(paths.into_iter().map(|path| ProfileLayer { profile: profile.clone(), path }));
+    Ok(())
+}
+
 impl fmt::Display for Config {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "target: {}", self.target)?;
+        if let Some(format) = self.format {
+            writeln!(f, "format: {}", format)?;
+        }
         let mut curr_profiles_str = self.current_profiles.join(", ");
         if curr_profiles_str.is_empty() {
             curr_profiles_str = "none".to_string();
         }
-        writeln!(f, "current: {}", curr_profiles_str)?;
+        if self.current_groups.is_empty() {
+            writeln!(f, "current: {}", curr_profiles_str)?;
+        } else {
+            writeln!(f, "current: {} (group: {})", curr_profiles_str, self.current_groups.join(", "))?;
+        }
         writeln!(f, "profiles:")?;
         
         for (name, profiles) in &self.profiles {
-            if let Some(profile) = profiles.first() {
-                writeln!(f, "  - {}: \"{}\"", name, profile.path)?;
+            if !profiles.is_empty() {
+                let paths: Vec<String> = profiles
+                    .iter()
+                    .filter(|p| !p.path.is_empty())
+                    .map(|p| format!("\"{}\"", p.path))
+                    .collect();
+                write!(f, "  - {}: {}", name, paths.join(", "))?;
+
+                let parents = get_profile_parents(self, name);
+                if !parents.is_empty() {
+                    write!(f, " (extends {})", parents.join(", "))?;
+                }
+                if is_profile_secret(self, name) {
+                    write!(f, " (secret)")?;
+                }
+                writeln!(f)?;
             }
         }
+
+        if !self.groups.is_empty() {
+            writeln!(f, "groups:")?;
+            for (name, members) in &self.groups {
+                writeln!(f, "  - {}: {}", name, members.join(", "))?;
+            }
+        }
+
+        if !self.secrets.is_empty() {
+            writeln!(f, "secrets: {}", self.secrets.join(", "))?;
+        }
+
+        if !self.members.is_empty() {
+            writeln!(f, "members: {}", self.members.join(", "))?;
+        }
         Ok(())
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Profile {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub path: String,
+
+    /// Profiles whose variables are applied before this profile's own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
+
+    /// Whether every variable in the profile is secret
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub secret: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl fmt::Display for Profile {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "- {}", self.path)
+        if self.path.is_empty() {
+            write!(f, "- extends: {}", self.extends.join(", "))?;
+        } else if self.extends.is_empty() {
+            write!(f, "- {}", self.path)?;
+        } else {
+            write!(f, "- {} (extends {})", self.path, self.extends.join(", "))?;
+        }
+        if self.secret {
+            write!(f, " (secret)")?;
+        }
+        Ok(())
     }
 }
 
@@ -116,4 +494,31 @@ where
     }
     
     map.end()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path_should_walk_up_and_down() {
+        assert_eq!(relative_path(Path::new("/repo"), Path::new("/repo/api/.env")), Path::new("api/.env"));
+        assert_eq!(relative_path(Path::new("/repo/api"), Path::new("/repo/web/./.env")), Path::new("../web/.env"));
+        assert_eq!(relative_path(Path::new("/repo"), Path::new("/repo/api/../.env")), Path::new(".env"));
+    }
+
+    #[test]
+    fn test_current_names_should_collapse_groups_in_order() {
+        let config: Config = serde_yaml::from_str(
+            "current_profiles: [local, base, dev, extra]\ncurrent_groups: [dev]\ngroups:\n  dev: [base, dev-db]\n  stack: [base, dev]",
+        )
+        .unwrap();
+        assert_eq!(current_names(&config), vec!["local", "base", "dev", "extra"]);
+
+        let config: Config = serde_yaml::from_str(
+            "current_profiles: [local, base, dev, extra]\ncurrent_groups: [stack]\ngroups:\n  stack: [base, dev]",
+        )
+        .unwrap();
+        assert_eq!(current_names(&config), vec!["local", "stack", "extra"]);
+    }
+}
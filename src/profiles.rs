@@ -1,61 +1,103 @@
 use anyhow::{anyhow, Result};
 
-use crate::{nvy_config::{does_config_exist, does_file_exist, load_config, save_config, Profile, CONFIG_FILE_NAME}, success, warn};
+use crate::{nvy_config::{does_config_exist, does_file_exist, get_profile_parents, is_profile_secret, load_config, lock_config, resolve_profile, save_config, Config, Profile, CONFIG_FILE_NAME}, success, warn};
+use crate::workspace::render_members;
 
 pub fn run_profiles() -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
     let config = load_config()?;
-    if config.profiles.is_empty() {
-        println!("No profiles defined.");
+    if config.members.is_empty() {
+        print!("{}", render_profiles(&config));
         return Ok(());
     }
 
-    println!("profiles:");
+    // A workspace often has no profiles of its own, only its members do
+    if !config.profiles.is_empty() {
+        print!("{}", render_profiles(&config));
+    }
+    print!("{}", render_members(&config, render_profiles)?);
+
+    Ok(())
+}
+
+fn render_profiles(config: &Config) -> String {
+    if config.profiles.is_empty() {
+        return "No profiles defined.\n".to_string();
+    }
+
+    let mut output = String::from("profiles:\n");
     for (name, profiles) in &config.profiles {
-        println!("  - {}:", name);
+        output.push_str(&format!("  - {}:\n", name));
         for profile in profiles {
-            println!("    {}", profile);
+            output.push_str(&format!("    {}\n", profile));
         }
     }
 
-    Ok(())
+    if !config.groups.is_empty() {
+        output.push_str("groups:\n");
+        for (name, members) in &config.groups {
+            output.push_str(&format!("  - {}: {}\n", name, members.join(", ")));
+        }
+    }
+
+    output
 }
 
-pub fn run_profiles_set(profile: &String, file: &String) -> Result<()> {
+pub fn run_profiles_set(profile: &String, files: &[String], extends: &[String], secret: bool) -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
-    if !does_file_exist(file) {
+    if let Some(file) = files.iter().find(|file| !does_file_exist(file)) {
         return Err(anyhow!(
             "File {} does not exist in the current directory.",
             file
         ));
     }
 
+    let _lock = lock_config()?;
     let mut config = load_config()?;
-    config.profiles.insert(
-        profile.clone(),
-        vec![Profile {
-            path: file.clone(),
-        }],
-    );
+
+    if let Some(parent) = extends.iter().find(|parent| !config.profiles.contains_key(*parent)) {
+        return Err(anyhow!("Profile {} does not exist in the {} file.", parent, CONFIG_FILE_NAME));
+    }
+
+    // Keep what the profile already extends unless new parents are given
+    let extends = if extends.is_empty() {
+        get_profile_parents(&config, profile)
+    } else {
+        extends.to_vec()
+    };
+
+    // Likewise, a profile stays secret once it has been marked as secret
+    let secret = secret || is_profile_secret(&config, profile);
+
+    let mut paths = Vec::new();
+    if !extends.is_empty() {
+        paths.push(Profile { path: String::new(), extends, secret: false });
+    }
+    paths.extend(files.iter().map(|file| Profile { path: file.clone(), extends: Vec::new(), secret: false }));
+    if let Some(first) = paths.first_mut() {
+        first.secret = secret;
+    }
+    config.profiles.insert(profile.clone(), paths);
+    resolve_profile(&config, profile)?;
 
     save_config(&config)?;
 
     success!(
-        "Set profile {} with path {}",
+        "Set profile {} with path(s) {}",
         profile,
-        file
+        files.join(", ")
     );
 
     Ok(())
@@ -64,11 +106,12 @@ pub fn run_profiles_set(profile: &String, file: &String) -> Result<()> {
 pub fn run_profiles_remove(profile: &String) -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
+    let _lock = lock_config()?;
     let mut config = load_config()?;
     if !config.profiles.contains_key(profile) {
         warn!("Profile {} does not exist.", profile);
@@ -0,0 +1,191 @@
+use crate::nvy_config::TARGET_SHELL;
+
+/// The shells that shell mode can output commands for, selected by the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Posix,
+    Fish,
+    Pwsh,
+    Nu,
+    Csh,
+}
+
+impl Shell {
+    pub fn from_target(target: &str) -> Option<Self> {
+        match target {
+            TARGET_SHELL => Some(Shell::Posix),
+            "fish" => Some(Shell::Fish),
+            "pwsh" => Some(Shell::Pwsh),
+            "nu" => Some(Shell::Nu),
+            "csh" => Some(Shell::Csh),
+            _ => None,
+        }
+    }
+
+    pub fn export(&self, key: &str, value: &str) -> String {
+        match self {
+            Shell::Posix => format!("export {}={}", key, self.quote(value)),
+            Shell::Fish => format!("set -gx {} {}", key, self.quote(value)),
+            Shell::Pwsh => format!("$env:{} = {}", key, self.quote(value)),
+            Shell::Nu => format!("$env.{} = {}", key, self.quote(value)),
+            Shell::Csh => format!("setenv {} {};", key, self.quote(value)),
+        }
+    }
+
+    pub fn unset(&self, key: &str) -> String {
+        match self {
+            Shell::Posix => format!("unset {}", key),
+            Shell::Fish => format!("set -e {}", key),
+            Shell::Pwsh => format!("Remove-Item -Path Env:{} -ErrorAction SilentlyContinue", key),
+            Shell::Nu => format!("hide-env -i {}", key),
+            Shell::Csh => format!("unsetenv {};", key),
+        }
+    }
+
+    /// Quote a value so that the shell reads it back unchanged
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            Shell::Posix => escape_shell_value(value),
+            Shell::Fish => escape_fish_value(value),
+            Shell::Pwsh => escape_pwsh_value(value),
+            Shell::Nu => escape_nu_value(value),
+            Shell::Csh => escape_csh_value(value),
+        }
+    }
+
+    /// A comment line, if the shell can safely evaluate one. csh only treats `#` as
+    /// a comment in scripts, so nothing is emitted for it.
+    pub fn comment(&self, text: &str) -> Option<String> {
+        match self {
+            Shell::Csh => None,
+            _ => Some(format!("# {}", text)),
+        }
+    }
+}
+
+fn escape_shell_value(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// fish single quotes only treat `\'` and `\\` as escapes
+fn escape_fish_value(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// PowerShell single quotes escape a quote by doubling it, and also treat the
+/// typographic single quotes as quote characters
+fn escape_pwsh_value(value: &str) -> String {
+    let mut escaped = String::from("'");
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            escaped.push(c);
+        }
+        escaped.push(c);
+    }
+    escaped.push('\'');
+    escaped
+}
+
+/// nushell single-quoted strings can't contain a `'`, so use a double-quoted string
+fn escape_nu_value(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// csh expands `!` history references and ends a command at a newline even inside
+/// single quotes, so both need a backslash
+fn escape_csh_value(value: &str) -> String {
+    let mut escaped = String::from("'");
+    for c in value.chars() {
+        match c {
+            '\'' => escaped.push_str("'\\''"),
+            '!' => escaped.push_str("\\!"),
+            '\n' => escaped.push_str("\\\n"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.push('\'');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_shell_value_should_wrap_basic_string_in_quotes() {
+        assert_eq!(escape_shell_value("hello"), "'hello'");
+    }
+
+    #[test]
+    fn test_escape_shell_value_should_escape_internal_single_quotes() {
+        assert_eq!(escape_shell_value("he'llo"), "'he'\\''llo'");
+    }
+
+    #[test]
+    fn test_escape_shell_value_should_preserve_internal_double_quotes() {
+        assert_eq!(escape_shell_value("he\"llo"), "'he\"llo'");
+    }
+
+    #[test]
+    fn test_escape_shell_value_should_handle_empty_string() {
+        assert_eq!(escape_shell_value(""), "''");
+    }
+
+    #[test]
+    fn test_escape_shell_value_should_preserve_surrounding_quotes_in_value() {
+        assert_eq!(escape_shell_value("\"hello\""), "'\"hello\"'");
+    }
+
+    #[test]
+    fn test_escape_shell_value_should_handle_multiple_internal_single_quotes() {
+        assert_eq!(escape_shell_value("it's O'clock"), "'it'\\''s O'\\''clock'");
+    }
+
+    #[test]
+    fn test_from_target_should_only_match_shells() {
+        assert_eq!(Shell::from_target("sh"), Some(Shell::Posix));
+        assert_eq!(Shell::from_target("fish"), Some(Shell::Fish));
+        assert_eq!(Shell::from_target("pwsh"), Some(Shell::Pwsh));
+        assert_eq!(Shell::from_target("nu"), Some(Shell::Nu));
+        assert_eq!(Shell::from_target("csh"), Some(Shell::Csh));
+        assert_eq!(Shell::from_target(".env.nvy"), None);
+    }
+
+    #[test]
+    fn test_fish_should_escape_quotes_and_backslashes() {
+        assert_eq!(Shell::Fish.export("KEY", "it's a \\ test"), "set -gx KEY 'it\\'s a \\\\ test'");
+        assert_eq!(Shell::Fish.unset("KEY"), "set -e KEY");
+    }
+
+    #[test]
+    fn test_pwsh_should_double_quotes() {
+        assert_eq!(Shell::Pwsh.export("KEY", "it's $HOME"), "$env:KEY = 'it''s $HOME'");
+        assert_eq!(Shell::Pwsh.export("KEY", "it\u{2019}s"), "$env:KEY = 'it\u{2019}\u{2019}s'");
+        assert_eq!(Shell::Pwsh.unset("KEY"), "Remove-Item -Path Env:KEY -ErrorAction SilentlyContinue");
+    }
+
+    #[test]
+    fn test_nu_should_use_escaped_double_quotes() {
+        assert_eq!(Shell::Nu.export("KEY", "it's \"a\"\nb\\c"), "$env.KEY = \"it's \\\"a\\\"\\nb\\\\c\"");
+        assert_eq!(Shell::Nu.unset("KEY"), "hide-env -i KEY");
+    }
+
+    #[test]
+    fn test_csh_should_escape_quotes_history_and_newlines() {
+        assert_eq!(Shell::Csh.export("KEY", "it's!\nnext"), "setenv KEY 'it'\\''s\\!\\\nnext';");
+        assert_eq!(Shell::Csh.unset("KEY"), "unsetenv KEY;");
+        assert_eq!(Shell::Csh.comment("profile"), None);
+    }
+}
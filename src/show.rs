@@ -0,0 +1,34 @@
+use anyhow::{anyhow, Result};
+
+use crate::nvy_config::{does_config_exist, load_config, CONFIG_FILE_NAME};
+use crate::r#use::{export_profiles, EnvVar};
+
+/// Print the variables the given profiles resolve to, and where each of them came from,
+/// without writing the target or changing the profiles in use
+pub fn run_show(profiles: &[String]) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    let result = export_profiles(&config, profiles, false)?;
+    if result.new_vars.is_empty() {
+        println!("Profile(s) {} don't define any variables.", profiles.join(", "));
+        return Ok(());
+    }
+
+    for var in &result.new_vars {
+        println!("{}={} ({})", var.key, var.display_value(), origin(var));
+        for previous in var.overridden.iter().rev() {
+            println!("  - overrides {} ({})", previous.display_value(), origin(previous));
+        }
+    }
+    Ok(())
+}
+
+fn origin(var: &EnvVar) -> String {
+    format!("{}, {}:{}", var.source_profile, var.path, var.line)
+}
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::diff::diff_vars;
+use crate::format::{parse, render, target_format};
+use crate::nvy_config::{does_config_exist, does_file_exist, is_target_shell, load_config, resolve_profile, Config, CONFIG_FILE_NAME};
+use crate::r#use::{export_profiles, unix_time};
+
+/// Report whether the target still matches what the current profiles produce, and which
+/// of their files changed since they were last used
+pub fn run_status() -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    if config.current_profiles.is_empty() {
+        println!("No profiles in use, run `nvy use <profile>` first.");
+        return Ok(());
+    }
+
+    let profiles = config.current_profiles.join(", ");
+    if is_target_shell(&config) {
+        println!("target: {} (shell)", config.target);
+    } else {
+        report_drift(&config, &profiles)?;
+    }
+
+    let changed = get_changed_sources(&config)?;
+    if !changed.is_empty() {
+        println!("changed since the last `nvy use`:");
+        for path in changed {
+            println!("  - {}", path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the target file on disk with what `nvy use` would write to it
+fn report_drift(config: &Config, profiles: &str) -> Result<()> {
+    if !does_file_exist(&config.target) {
+        println!("target: {} does not exist, run `nvy use` to create it.", config.target);
+        return Ok(());
+    }
+
+    let format = target_format(config);
+    let result = export_profiles(config, &config.current_profiles, false)?;
+    let expected = render(format, &result, &config.target)?;
+    let actual = fs::read_to_string(&config.target)?;
+
+    if actual == expected {
+        println!("target: {} is up to date with {}", config.target, profiles);
+        return Ok(());
+    }
+
+    let (Some(expected_vars), Some(actual_vars)) = (parse(format, &expected), parse(format, &actual)) else {
+        println!("target: {} has been modified since using {}", config.target, profiles);
+        return Ok(());
+    };
+
+    let diff = diff_vars(&expected_vars, &actual_vars);
+    if diff.is_empty() {
+        println!("target: {} is up to date with {} (formatting differs)", config.target, profiles);
+        return Ok(());
+    }
+
+    println!("target: {} has drifted from {}", config.target, profiles);
+    if !diff.only_in_b.is_empty() {
+        println!("added:");
+        for (key, _) in &diff.only_in_b {
+            println!("  - {}", key);
+        }
+    }
+    if !diff.only_in_a.is_empty() {
+        println!("removed:");
+        for (key, _) in &diff.only_in_a {
+            println!("  - {}", key);
+        }
+    }
+    if !diff.changed.is_empty() {
+        println!("changed:");
+        for (key, _, _) in &diff.changed {
+            println!("  - {}", key);
+        }
+    }
+
+    Ok(())
+}
+
+/// The files of the current profiles that were modified after they were last used
+fn get_changed_sources(config: &Config) -> Result<Vec<String>> {
+    let Some(last_used) = config.last_used else {
+        return Ok(Vec::new());
+    };
+
+    let mut changed = Vec::new();
+    for profile in &config.current_profiles {
+        for layer in resolve_profile(config, profile)? {
+            if changed.contains(&layer.path) {
+                continue;
+            }
+            let is_changed = match config.source_hashes.get(&layer.path) {
+                Some(hash) => hash_file(&layer.path).as_ref() != Some(hash),
+                // Configs from before the hashes were recorded only have the time to go by
+                None => fs::metadata(&layer.path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| unix_time(modified) >= last_used),
+            };
+            if is_changed {
+                changed.push(layer.path);
+            }
+        }
+    }
+    Ok(changed)
+}
+
+/// The hash of each file of the given profiles, to tell later whether any of them changed
+pub(crate) fn hash_sources(config: &Config, profiles: &[String]) -> BTreeMap<String, String> {
+    profiles
+        .iter()
+        .flat_map(|profile| resolve_profile(config, profile).unwrap_or_default())
+        .filter_map(|layer| Some((layer.path.clone(), hash_file(&layer.path)?)))
+        .collect()
+}
+
+fn hash_file(path: &str) -> Option<String> {
+    fs::read(path).ok().map(|contents| format!("{:x}", Sha256::digest(contents)))
+}
@@ -1,31 +1,37 @@
 use anyhow::{anyhow, Result};
 
-use crate::{nvy_config::{does_config_exist, load_config, save_config,CONFIG_FILE_NAME}, success};
+use crate::format::Format;
+use crate::{nvy_config::{does_config_exist, load_config, lock_config, save_config,CONFIG_FILE_NAME}, success};
 
 pub fn run_target() -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
     let config = load_config()?;
     println!("target: {}", config.target);
+    if let Some(format) = config.format {
+        println!("format: {}", format);
+    }
 
     Ok(())
 }
 
-pub fn run_target_set(file: &String) -> Result<()> {
+pub fn run_target_set(file: &String, format: Option<Format>) -> Result<()> {
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
+    let _lock = lock_config()?;
     let mut config = load_config()?;
     config.target = file.to_string();
+    config.format = format;
     save_config(&config)?;
 
     let msg = format!("Target set to {}", file);
@@ -1,268 +1,540 @@
 use anyhow::{anyhow, Result};
-use std::{collections::{BTreeMap, HashSet}, fs};
-
-use crate::{nvy_config::{does_config_exist, get_profile_path, is_target_shell, load_config, save_config, Config, CONFIG_FILE_NAME}, success};
-
-#[derive(Debug)]
-struct EnvVar {
-    key: String,
-    value: Option<String>,
-    source_profile: String,
+use similar::TextDiff;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{self, IsTerminal};
+use std::{collections::BTreeSet, fmt, fs};
+
+use crate::backup::backup_target;
+use crate::crypto::read_profile_file;
+use crate::env::{EnvFile, Variable};
+use crate::format::{render, target_format, Format};
+use crate::interpolate::{interpolate, RawValue};
+use crate::shell::Shell;
+use crate::status::hash_sources;
+use crate::workspace::run_use_members;
+use crate::{nvy_config::{does_config_exist, does_file_exist, expand_groups, is_profile_secret, load_config, lock_config, resolve_profile, save_config, write_atomic, Config, ProfileLayer, CONFIG_FILE_NAME, SECRET_ANNOTATION, SECRET_MASK}, error, success, warn};
+
+#[derive(Debug, Clone)]
+pub(crate) struct EnvVar {
+    pub(crate) key: String,
+    pub(crate) value: String,
+    pub(crate) source_profile: String,
+    /// The file and 1-based line the variable was defined on
+    pub(crate) path: String,
+    pub(crate) line: usize,
+    /// Earlier definitions of the same variable that this one replaced, oldest first
+    pub(crate) overridden: Vec<EnvVar>,
+    /// Whether the value was single-quoted, and so must not be interpolated
+    literal: bool,
+    /// Whether the value must never be shown
+    pub(crate) secret: bool,
 }
 
 impl EnvVar {
-    fn new(key: String, value: Option<String>, source_profile: String) -> Self {
-        Self { key, value, source_profile }
+    fn from_variable(var: &Variable, layer: &ProfileLayer, secret: bool) -> Self {
+        Self {
+            key: var.key().to_string(),
+            value: var.value().to_string(),
+            source_profile: layer.profile.clone(),
+            path: layer.path.clone(),
+            line: var.line(),
+            overridden: Vec::new(),
+            literal: var.is_literal(),
+            secret,
+        }
     }
 
-    fn is_valid(&self) -> bool {
-        self.key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    /// The value to show to the user, which is masked if it is secret
+    pub(crate) fn display_value(&self) -> &str {
+        if self.secret {
+            SECRET_MASK
+        } else {
+            &self.value
+        }
     }
 
-    fn to_shell_command(&self) -> String {
-        match &self.value {
-            Some(val) => format!("export {}={}", display_key(&self.key), escape_shell_value(val)),
-            None => format!("unset {}", display_key(&self.key)),
-        }
+    fn to_shell_command(&self, shell: Shell, mask_secrets: bool) -> String {
+        let value = if mask_secrets { self.display_value() } else { &self.value };
+        shell.export(&self.key, value)
     }
+}
+
+/// A line of a profile file that could not be exported
+#[derive(Debug, PartialEq)]
+struct Diagnostic {
+    path: String,
+    /// 1-based line number
+    line: usize,
+    reason: String,
+}
 
-    fn to_env_file_line(&self) -> Option<String> {
-        self.value.as_ref().map(|val| format!("{}={}", display_key(&self.key), val))
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.reason)
     }
 }
 
-struct ExportResult {
-    unset_vars: BTreeMap<String, EnvVar>,
-    new_vars: BTreeMap<String, EnvVar>,
-    profile_order: Vec<String>,
+/// The merged result of exporting one or more profiles
+pub(crate) struct ExportResult {
+    /// The exported variables, in profile order and then in the order they appear in each file
+    pub(crate) new_vars: Vec<EnvVar>,
+    pub(crate) profile_order: Vec<String>,
+    /// The profiles that were exported, after expanding any groups
+    pub(crate) profiles: Vec<String>,
+    pub(crate) groups: Vec<String>,
 }
 
-pub fn run_use(profiles: &Vec<String>) -> Result<()> {
+#[derive(Default)]
+pub struct UseOptions {
+    /// Fail the whole export if any line of a profile is malformed
+    pub strict: bool,
+    /// Print what would change instead of writing the target and the nvy configuration
+    pub dry_run: bool,
+    /// Use the profile(s) in every member of the workspace instead
+    pub all: bool,
+    /// Use the profile(s) in these members of the workspace instead
+    pub members: Vec<String>,
+}
+
+pub fn run_use(profiles: &[String], options: &UseOptions) -> Result<()> {
+    if options.all || !options.members.is_empty() {
+        return run_use_members(profiles, options);
+    }
+
     if !does_config_exist() {
         return Err(anyhow!(
-            "{} does not exist in the current directory, please run `nvy init` first.",
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
             CONFIG_FILE_NAME
         ));
     }
 
-    let mut result = ExportResult {
-        unset_vars: BTreeMap::new(),
-        new_vars: BTreeMap::new(),
-        profile_order: Vec::new(),
-    };
-
+    let _lock = lock_config()?;
     let mut config = load_config()?;
+    let result = export_profiles(&config, profiles, options.strict)?;
+    let unset_vars = get_current_profile_vars(&config);
+    if options.dry_run {
+        return preview_use(&config, profiles, &unset_vars, &result);
+    }
 
-    for profile in profiles {
-        let profile_str = profile.to_string();
-        result.profile_order.push(profile_str.clone());
+    config.current_profiles = result.profiles.clone();
+    config.current_groups = result.groups.clone();
+    config.last_used = Some(unix_time(SystemTime::now()));
+    config.source_hashes = hash_sources(&config, &result.profiles);
+    save_config(&config)?;
 
-        let profile_vars = export_profile(&config, profile)?;
-        result.unset_vars.extend(profile_vars.unset_vars);
-        result.new_vars.extend(profile_vars.new_vars);
+    if let Some(shell) = Shell::from_target(&config.target) {
+        // Output that is shown rather than evaluated shouldn't reveal any secrets
+        let mask_secrets = io::stdout().is_terminal();
+        if mask_secrets && result.new_vars.iter().any(|var| var.secret) {
+            warn!("Secret values are masked, as the output is not being evaluated by a shell.");
+        }
+        print!("{}", to_shell_commands(shell, &unset_vars, &result, mask_secrets));
+    } else {
+        let content = render(target_format(&config), &result, &config.target)?;
+        backup_target(&config.target, &content)?;
+        write_atomic(&config.target, content)?;
+        success!("Exported profile(s) {} to file {}", profiles.join(", "), config.target);
     }
 
-    config.current_profiles = profiles.clone();
-    save_config(&config)?;
+    Ok(())
+}
 
-    if is_target_shell(&config) {
-        for (_, var) in result.unset_vars {
-            println!("{}", var.to_shell_command());
+/// Print what `nvy use` would do, as a diff against the target in file mode
+fn preview_use(config: &Config, profiles: &[String], unset_vars: &BTreeSet<String>, result: &ExportResult) -> Result<()> {
+    let mask_secrets = io::stdout().is_terminal();
+    if let Some(shell) = Shell::from_target(&config.target) {
+        print!("{}", to_shell_commands(shell, unset_vars, result, mask_secrets));
+        return Ok(());
+    }
+
+    let current = if does_file_exist(&config.target) {
+        fs::read_to_string(&config.target)?
+    } else {
+        String::new()
+    };
+    let content = render(target_format(config), result, &config.target)?;
+    if current == content {
+        println!("{} is already up to date with {}", config.target, profiles.join(", "));
+        return Ok(());
+    }
+
+    let (current, content) = if mask_secrets {
+        let format = target_format(config);
+        let secret_keys = get_secret_keys(config, result);
+        (mask_secret_lines(format, &current, &secret_keys, &config.target)?, render(format, &masked(result), &config.target)?)
+    } else {
+        (current, content)
+    };
+    if current == content {
+        println!("{} would only change the values of secrets, which are masked.", config.target);
+        return Ok(());
+    }
+
+    let new_header = format!("{} (nvy use {})", config.target, profiles.join(" "));
+    print!(
+        "{}",
+        TextDiff::from_lines(&current, &content)
+            .unified_diff()
+            .header(&config.target, &new_header)
+    );
+    Ok(())
+}
+
+/// The exported variables with secret values replaced by the mask
+fn masked(result: &ExportResult) -> ExportResult {
+    ExportResult {
+        new_vars: result
+            .new_vars
+            .iter()
+            .map(|var| EnvVar { value: var.display_value().to_string(), ..var.clone() })
+            .collect(),
+        profile_order: result.profile_order.clone(),
+        profiles: result.profiles.clone(),
+        groups: result.groups.clone(),
+    }
+}
+
+/// The variables that are secret now, or were secret in the profiles the target was written from
+fn get_secret_keys(config: &Config, result: &ExportResult) -> BTreeSet<String> {
+    let mut keys: BTreeSet<String> = config.secrets.iter().cloned().collect();
+    let previous = export_profiles(config, &config.current_profiles, false).ok();
+    for var in result.new_vars.iter().chain(previous.iter().flat_map(|previous| &previous.new_vars)) {
+        if var.secret {
+            keys.insert(var.key.clone());
         }
-        
-        let mut profile_groups: BTreeMap<String, BTreeMap<String, &EnvVar>> = BTreeMap::new();
-        for var in result.new_vars.values() {
-            profile_groups
-                .entry(var.source_profile.clone())
-                .or_default()
-                .insert(var.key.clone(), var);
+    }
+    keys
+}
+
+/// Mask the value of each definition of a secret in the target, one whole definition at a
+/// time, the way `render` would write the mask, so that no other line is touched
+fn mask_secret_lines(format: Format, content: &str, secret_keys: &BTreeSet<String>, target: &str) -> Result<String> {
+    let mut masked = String::new();
+    let mut lines = content.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some(key) = secret_keys.iter().find(|key| defines_key(line, key)) else {
+            masked.push_str(line);
+            masked.push('\n');
+            continue;
+        };
+
+        let indent = &line[..line.len() - line.trim_start().len()];
+        masked.push_str(indent);
+        masked.push_str(&render_masked_line(format, key, target)?);
+        if format == Format::Json && line.ends_with(',') {
+            masked.push(',');
         }
-        
-        for profile in result.profile_order {
-            if let Some(vars) = profile_groups.get(&profile) {
-                if !vars.is_empty() {
-                    println!("# {}", profile);
-                    for (_, var) in vars {
-                        println!("{}", var.to_shell_command());
+        masked.push('\n');
+
+        // Drop the rest of a value that spans several lines
+        let value = line.split_once(['=', ':']).map(|(_, value)| value.trim()).unwrap_or_default();
+        match format {
+            Format::Env | Format::Systemd => {
+                if let Some(quote @ ('"' | '\'')) = value.chars().next() {
+                    let is_closed = |text: &str| text.ends_with(quote) && !text.ends_with(&format!("\\{}", quote));
+                    if value.len() == 1 || !is_closed(value) {
+                        for line in lines.by_ref() {
+                            if is_closed(line.trim_end()) {
+                                break;
+                            }
+                        }
                     }
-                    println!();
                 }
             }
+            Format::Yaml | Format::ConfigMap | Format::Secret if value.starts_with(['|', '>']) => {
+                while lines.peek().is_some_and(|next| next.len() - next.trim_start().len() > indent.len()) {
+                    lines.next();
+                }
+            }
+            _ => {}
         }
-    } else {
-        let mut content = String::new();
-        
-        let mut profile_groups: BTreeMap<String, BTreeMap<String, &EnvVar>> = BTreeMap::new();
-        for var in result.new_vars.values() {
-            profile_groups
-                .entry(var.source_profile.clone())
-                .or_default()
-                .insert(var.key.clone(), var);
+    }
+    if !content.ends_with('\n') {
+        masked.pop();
+    }
+    Ok(masked)
+}
+
+/// Whether a line of a target starts the definition of a variable, e.g. `KEY=`, `KEY: `,
+/// `"KEY": ` or `export KEY := `
+fn defines_key(line: &str, key: &str) -> bool {
+    let line = line.trim_start();
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let rest = line
+        .strip_prefix(key)
+        .or_else(|| line.strip_prefix(&format!("\"{}\"", key)))
+        .or_else(|| line.strip_prefix(&format!("'{}'", key)));
+    rest.is_some_and(|rest| rest.trim_start().starts_with(['=', ':']))
+}
+
+/// The line `render` writes for a variable whose value is the mask, without its indentation
+fn render_masked_line(format: Format, key: &str, target: &str) -> Result<String> {
+    let var = EnvVar {
+        key: key.to_string(),
+        value: SECRET_MASK.to_string(),
+        source_profile: String::new(),
+        path: String::new(),
+        line: 0,
+        overridden: Vec::new(),
+        literal: true,
+        secret: false,
+    };
+    let result = ExportResult {
+        new_vars: vec![var],
+        profile_order: vec![String::new()],
+        profiles: Vec::new(),
+        groups: Vec::new(),
+    };
+    let content = render(format, &result, target)?;
+    Ok(content
+        .lines()
+        .find(|line| defines_key(line, key))
+        .map(|line| line.trim().to_string())
+        .unwrap_or_else(|| format!("{}={}", key, SECRET_MASK)))
+}
+
+/// Merge the given profiles (or groups) into the variables that `nvy use` would export,
+/// without writing anything
+pub(crate) fn export_profiles(config: &Config, profiles: &[String], strict: bool) -> Result<ExportResult> {
+    let (expanded, groups) = expand_groups(config, profiles)?;
+    let mut result = ExportResult {
+        new_vars: Vec::new(),
+        profile_order: Vec::new(),
+        profiles: expanded.clone(),
+        groups,
+    };
+    let mut diagnostics = Vec::new();
+
+    for profile in &expanded {
+        let profile_vars = export_profile(config, profile, &mut diagnostics)?;
+        for name in profile_vars.profile_order {
+            if !result.profile_order.contains(&name) {
+                result.profile_order.push(name);
+            }
         }
-        
-        for profile in result.profile_order {
-            if let Some(vars) = profile_groups.get(&profile) {
-                if !vars.is_empty() {
-                    content.push_str(&format!("# {}\n", profile));
-                    for (_, var) in vars {
-                        if let Some(line) = var.to_env_file_line() {
-                            content.push_str(&line);
-                            content.push('\n');
-                        }
-                    }
-                    content.push('\n');
+
+        for mut var in profile_vars.new_vars {
+            // Last one wins: replace the value from any earlier definition, remembering it
+            if let Some(pos) = result.new_vars.iter().position(|existing| existing.key == var.key) {
+                let mut previous = result.new_vars.remove(pos);
+                var.overridden = std::mem::take(&mut previous.overridden);
+                var.overridden.push(previous);
+            }
+            // A key that is secret in any of its definitions is secret in all of them
+            if var.secret || var.overridden.iter().any(|previous| previous.secret) {
+                var.secret = true;
+                for previous in &mut var.overridden {
+                    previous.secret = true;
                 }
             }
+            result.new_vars.push(var);
         }
-        
-        content = content.trim_end().to_string();
-        content.push('\n');
-        
-        let target = config.target.clone();
-        fs::write(config.target, content)?;
-        success!("Exported profile(s) {} to file {}", profiles.join(", "), target);
     }
 
-    Ok(())
+    report_diagnostics(&diagnostics, strict)?;
+    interpolate_vars(&mut result.new_vars)?;
+    Ok(result)
 }
 
-fn export_profile(config: &Config, profile: &String) -> Result<ExportResult> {
-    let new_path = get_profile_path(config, profile)?;
+/// Render the commands that unset the given variables and then export the result,
+/// grouped under a comment for each profile
+pub(crate) fn to_shell_commands(
+    shell: Shell,
+    unset_vars: &BTreeSet<String>,
+    result: &ExportResult,
+    mask_secrets: bool,
+) -> String {
+    let mut commands = String::new();
+    for key in unset_vars {
+        commands.push_str(&shell.unset(key));
+        commands.push('\n');
+    }
+
+    for profile in &result.profile_order {
+        let mut vars = result.new_vars.iter().filter(|var| &var.source_profile == profile).peekable();
+        if vars.peek().is_some() {
+            if let Some(comment) = shell.comment(profile) {
+                commands.push_str(&comment);
+                commands.push('\n');
+            }
+            for var in vars {
+                commands.push_str(&var.to_shell_command(shell, mask_secrets));
+                commands.push('\n');
+            }
+            commands.push('\n');
+        }
+    }
+    commands
+}
 
-    if !does_file_exist(&new_path) {
+fn export_profile(config: &Config, profile: &String, diagnostics: &mut Vec<Diagnostic>) -> Result<ExportResult> {
+    let layers = resolve_profile(config, profile)?;
+
+    if let Some(missing) = layers.iter().find(|layer| !does_file_exist(&layer.path)) {
         return Err(anyhow!(
             "Provided path {} under profile {} does not exist.",
-            new_path,
-            profile
+            missing.path,
+            missing.profile
         ));
     }
 
-    let unset_vars = get_current_profile_vars()?
-        .into_iter()
-        .map(|key| (key.clone(), EnvVar::new(key, None, "".to_string())))
-        .collect();
+    let mut new_vars = Vec::new();
+    let mut profile_order: Vec<String> = Vec::new();
+    for layer in layers {
+        if !profile_order.contains(&layer.profile) {
+            profile_order.push(layer.profile.clone());
+        }
 
-        let new_vars = parse_env_file(&new_path, profile)?
-        .into_iter()
-        .filter(|var| {
-            let actual_key = var.key.split_once('_').map(|(_, k)| k).unwrap_or(&var.key);
-            actual_key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
-        })
-        .map(|var| (var.key.clone(), var))
-        .collect();
+        let file = EnvFile::parse(&read_profile_file(&layer.path)?);
+        let profile_secret = is_profile_secret(config, &layer.profile);
+        let annotated = file.annotated_keys(SECRET_ANNOTATION);
+        new_vars.extend(file.vars().map(|var| {
+            let secret = profile_secret
+                || annotated.contains(&var.key())
+                || config.secrets.iter().any(|key| key == var.key());
+            EnvVar::from_variable(var, &layer, secret)
+        }));
+        diagnostics.extend(
+            file.issues()
+                .iter()
+                .map(|issue| Diagnostic { path: layer.path.clone(), line: issue.line, reason: issue.reason.clone() }),
+        );
+    }
 
     Ok(ExportResult {
-        unset_vars,
         new_vars,
-        profile_order: Vec::new(),
+        profile_order,
+        profiles: vec![profile.clone()],
+        groups: Vec::new(),
     })
 }
 
-fn display_key(key: &str) -> &str {
-    key.split_once('_').map(|(_, k)| k).unwrap_or(key)
+/// Warn about every malformed line, or fail on them in strict mode
+fn report_diagnostics(diagnostics: &[Diagnostic], strict: bool) -> Result<()> {
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+
+    if strict {
+        for diagnostic in diagnostics {
+            error!("{}", diagnostic);
+        }
+        return Err(anyhow!(
+            "Found {} invalid line(s), refusing to export in strict mode.",
+            diagnostics.len()
+        ));
+    }
+
+    for diagnostic in diagnostics {
+        warn!("{}", diagnostic);
+    }
+    Ok(())
 }
 
-fn escape_shell_value(value: &str) -> String {
-    // trim any surrounding quotes if they exist
-    let clean_value = value.trim_matches(|c| c == '"' || c == '\'');
-    format!("'{}'", clean_value.replace('\'', "'\\''"))
+/// Expand variable references against the merged variables of all profiles
+fn interpolate_vars(vars: &mut [EnvVar]) -> Result<()> {
+    let raw = vars
+        .iter()
+        .map(|var| (var.key.clone(), RawValue { value: var.value.clone(), literal: var.literal }))
+        .collect();
+
+    let expanded = interpolate(&raw)?;
+    for var in vars.iter_mut() {
+        if let Some(value) = expanded.get(&var.key) {
+            var.value = value.clone();
+        }
+    }
+    Ok(())
 }
 
-fn does_file_exist(path: &str) -> bool {
-    fs::metadata(path).is_ok()
+/// Seconds since the Unix epoch, as stored in `last_used`, with the fraction of a second so
+/// that a file changed in the same second as `nvy use` is still noticed
+pub(crate) fn unix_time(time: SystemTime) -> f64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or_default()
 }
 
-fn get_current_profile_vars() -> Result<HashSet<String>> {
-    let mut vars = HashSet::new();
-    
-    let config = match load_config() {
-        Ok(cfg) => cfg,
-        Err(_) => return Ok(vars),
-    };
+/// The variables exported by the currently active profiles, which need to be unset
+/// before switching
+fn get_current_profile_vars(config: &Config) -> BTreeSet<String> {
+    let mut vars = BTreeSet::new();
 
     for profile in &config.current_profiles {
-        let path = match get_profile_path(&config, profile) {
-            Ok(p) => p,
+        let layers = match resolve_profile(config, profile) {
+            Ok(l) => l,
             Err(_) => continue,
         };
         
-        let contents = match fs::read_to_string(&path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-        
-        for line in parse_env_line(&contents, profile) {
-            if line.is_valid() {
-                vars.insert(line.key);
+        for layer in layers {
+            let file = match read_profile_file(&layer.path) {
+                Ok(contents) => EnvFile::parse(&contents),
+                Err(_) => continue,
+            };
+
+            for var in file.vars() {
+                vars.insert(var.key().to_string());
             }
         }
     }
     
-    Ok(vars)
-}
-
-fn parse_env_file(path: &str, profile: &str) -> Result<Vec<EnvVar>> {
-    let contents = fs::read_to_string(path)?;
-    Ok(parse_env_line(&contents, profile).collect())
-}
-
-fn parse_env_line<'a>(contents: &'a str, profile: &'a str) -> impl Iterator<Item = EnvVar> + 'a {
-    contents.lines().enumerate().filter_map(move |(idx, line)| {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            return None;
-        }
-
-        let (key, value) = line.split_once('=')?;
-        Some(EnvVar::new(
-            format!("{:010}_{}", idx, key.trim()),  // Pad with zeros for proper sorting
-            Some(value.trim().to_string()),
-            profile.to_string()
-        ))
-    })
+    vars
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_escape_shell_value_should_wrap_basic_string_in_quotes() {
-        assert_eq!(escape_shell_value("hello"), "'hello'");
-    }
-
-    #[test]
-    fn test_escape_shell_value_should_remove_existing_double_quotes() {
-        assert_eq!(escape_shell_value("\"hello\""), "'hello'");
+    fn parse_single(contents: &str) -> EnvVar {
+        let file = EnvFile::parse(contents);
+        let layer = ProfileLayer { profile: "test".to_string(), path: ".env".to_string() };
+        let mut vars: Vec<EnvVar> = file.vars().map(|var| EnvVar::from_variable(var, &layer, false)).collect();
+        assert_eq!(vars.len(), 1);
+        vars.remove(0)
     }
 
     #[test]
-    fn test_escape_shell_value_should_remove_existing_single_quotes() {
-        assert_eq!(escape_shell_value("'hello'"), "'hello'");
+    fn test_shell_command_should_not_include_double_quotes_from_file() {
+        assert_eq!(parse_single("KEY=\"hello\"").to_shell_command(Shell::Posix, false), "export KEY='hello'");
     }
 
     #[test]
-    fn test_escape_shell_value_should_escape_internal_single_quotes() {
-        assert_eq!(escape_shell_value("he'llo"), "'he'\\''llo'");
+    fn test_shell_command_should_not_include_single_quotes_from_file() {
+        assert_eq!(parse_single("KEY='hello'").to_shell_command(Shell::Posix, false), "export KEY='hello'");
     }
 
     #[test]
-    fn test_escape_shell_value_should_preserve_internal_double_quotes() {
-        assert_eq!(escape_shell_value("he\"llo"), "'he\"llo'");
+    fn test_shell_command_should_handle_mixed_quotes_from_file() {
+        assert_eq!(parse_single("KEY=\"hello'world\"").to_shell_command(Shell::Posix, false), "export KEY='hello'\\''world'");
     }
 
     #[test]
-    fn test_escape_shell_value_should_handle_empty_string() {
-        assert_eq!(escape_shell_value(""), "''");
+    fn test_mask_secret_lines_should_only_mask_secret_definitions() {
+        let keys = BTreeSet::from(["TOKEN".to_string()]);
+        let content = "# default\nAPP_ENV=develop\nHOME_URL=https://example.com\nTOKEN=e\n";
+        assert_eq!(
+            mask_secret_lines(Format::Env, content, &keys, ".env.nvy").unwrap(),
+            "# default\nAPP_ENV=develop\nHOME_URL=https://example.com\nTOKEN=********\n"
+        );
     }
 
     #[test]
-    fn test_escape_shell_value_should_handle_mixed_quotes() {
-        assert_eq!(escape_shell_value("\"hello'world\""), "'hello'\\''world'");
+    fn test_mask_secret_lines_should_mask_multiline_values() {
+        let keys = BTreeSet::from(["KEY".to_string()]);
+        assert_eq!(
+            mask_secret_lines(Format::Env, "KEY=\"line 1\nline 2\"\nOTHER=1\n", &keys, ".env.nvy").unwrap(),
+            "KEY=********\nOTHER=1\n"
+        );
+        assert_eq!(
+            mask_secret_lines(Format::Yaml, "KEY: |-\n  line 1\n  line 2\nOTHER: '1'\n", &keys, "env.yaml").unwrap(),
+            "KEY: '********'\nOTHER: '1'\n"
+        );
     }
 
     #[test]
-    fn test_escape_shell_value_should_handle_multiple_internal_single_quotes() {
-        assert_eq!(escape_shell_value("it's O'clock"), "'it'\\''s O'\\''clock'");
+    fn test_mask_secret_lines_should_match_rendered_json() {
+        let keys = BTreeSet::from(["A".to_string()]);
+        assert_eq!(
+            mask_secret_lines(Format::Json, "{\n  \"A\": \"e\",\n  \"B\": \"e\"\n}\n", &keys, "env.json").unwrap(),
+            "{\n  \"A\": \"********\",\n  \"B\": \"e\"\n}\n"
+        );
     }
 }
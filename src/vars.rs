@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Result};
+use std::io::{self, IsTerminal};
+
+use crate::allow::keep_allowed;
+use crate::crypto::is_encrypted;
+use crate::env::EnvFile;
+use crate::nvy_config::{current_names, does_config_exist, get_profile_paths, load_config, Config, CONFIG_FILE_NAME};
+use crate::r#use::{export_profiles, run_use, UseOptions};
+use crate::{success, warn};
+
+/// Print the value of a variable, as resolved from a profile or else the current profiles
+pub fn run_get(key: &str, profile: Option<&String>) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    let profiles = match profile {
+        Some(profile) => vec![profile.clone()],
+        None if config.current_profiles.is_empty() => {
+            return Err(anyhow!("No profiles in use, pass --profile or run `nvy use <profile>` first."));
+        }
+        None => config.current_profiles.clone(),
+    };
+
+    let result = export_profiles(&config, &profiles, false)?;
+    let Some(var) = result.new_vars.iter().find(|var| var.key == key) else {
+        return Err(anyhow!("Variable {} is not defined in profile(s) {}.", key, profiles.join(", ")));
+    };
+
+    // Like `nvy use`, only reveal a secret when the output is not shown on the terminal
+    if io::stdout().is_terminal() {
+        println!("{}", var.display_value());
+    } else {
+        println!("{}", var.value);
+    }
+    Ok(())
+}
+
+/// Set a variable in a profile's file, keeping the rest of the file as it is
+pub fn run_set(assignment: &str, profile: &String, apply: bool) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let Some((key, value)) = assignment.split_once('=') else {
+        return Err(anyhow!("Expected KEY=VALUE but found {}.", assignment));
+    };
+
+    let config = load_config()?;
+    let paths = get_profile_paths(&config, profile)?;
+    let files = read_files(&paths)?;
+    if files.is_empty() {
+        return Err(anyhow!("Profile {} has no files of its own to set {} in.", profile, key));
+    }
+
+    // Update the file that currently wins for the key, or else add it to the last file
+    let index = files
+        .iter()
+        .rposition(|(_, file)| file.get(key).is_some())
+        .unwrap_or(files.len() - 1);
+    let (path, mut file) = files.into_iter().nth(index).unwrap();
+
+    file.set(key, value)?;
+    keep_allowed(|| file.write(&path))?;
+    success!("Set {} in {}", key, path);
+
+    if apply {
+        apply_current_profiles(&config)?;
+    }
+    Ok(())
+}
+
+/// Remove a variable from every file of a profile
+pub fn run_unset(key: &str, profile: &String, apply: bool) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_config()?;
+    let paths = get_profile_paths(&config, profile)?;
+
+    let mut removed = Vec::new();
+    for (path, mut file) in read_files(&paths)? {
+        if file.remove(key) {
+            keep_allowed(|| file.write(&path))?;
+            removed.push(path);
+        }
+    }
+
+    if removed.is_empty() {
+        warn!("Variable {} is not defined in profile {}.", key, profile);
+        return Ok(());
+    }
+    success!("Unset {} in {}", key, removed.join(", "));
+
+    if apply {
+        apply_current_profiles(&config)?;
+    }
+    Ok(())
+}
+
+/// Read the files of a profile for editing, which can't be done to encrypted files
+fn read_files(paths: &[String]) -> Result<Vec<(String, EnvFile)>> {
+    paths
+        .iter()
+        .map(|path| {
+            if is_encrypted(path) {
+                return Err(anyhow!("{} is encrypted, so it can't be edited in place.", path));
+            }
+            Ok((path.clone(), EnvFile::read(path)?))
+        })
+        .collect()
+}
+
+/// Export the current profiles again, so that the target picks up the change
+fn apply_current_profiles(config: &Config) -> Result<()> {
+    if config.current_profiles.is_empty() {
+        warn!("No profiles in use, so there is nothing to apply.");
+        return Ok(());
+    }
+    run_use(&current_names(config), &UseOptions::default())
+}
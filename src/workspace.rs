@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Context, Result};
+use std::env;
+
+use crate::nvy_config::{
+    does_config_exist, is_target_shell, load_config, load_config_from, set_config_path, Config, CONFIG_FILE_NAME,
+};
+use crate::r#use::{run_use, UseOptions};
+use crate::warn;
+
+/// Use the profile(s) in each of the selected members of the workspace. With `--all`,
+/// members that can't use them are skipped, while a member asked for by name must use them.
+pub(crate) fn run_use_members(profiles: &[String], options: &UseOptions) -> Result<()> {
+    if !does_config_exist() {
+        return Err(anyhow!(
+            "{} does not exist in the current directory or its parents, please run `nvy init` first.",
+            CONFIG_FILE_NAME
+        ));
+    }
+
+    let config = load_workspace()?;
+    let members = select_members(&config, &options.members)?;
+    let member_options = UseOptions {
+        strict: options.strict,
+        dry_run: options.dry_run,
+        ..UseOptions::default()
+    };
+
+    for member in &members {
+        in_member(member, || {
+            let config = load_config()?;
+            let problem = if is_target_shell(&config) {
+                Some("Its target is the shell".to_string())
+            } else if !defines_profiles(&config, profiles) {
+                Some(format!("It doesn't define {}", profiles.join(", ")))
+            } else {
+                None
+            };
+
+            if let Some(problem) = problem {
+                if options.all {
+                    warn!("Skipping member {}. {}.", member, problem);
+                    return Ok(());
+                }
+                return Err(anyhow!("{}.", problem));
+            }
+
+            println!("member {}:", member);
+            run_use(profiles, &member_options)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The config of each member of the workspace, rendered and indented below its name
+pub(crate) fn render_members(config: &Config, render: impl Fn(&Config) -> String) -> Result<String> {
+    let mut output = String::new();
+    for member in &config.members {
+        let rendered = in_member(member, || Ok(render(&load_config()?)))?;
+        output.push_str(&format!("member {}:\n", member));
+        for line in rendered.lines() {
+            if line.is_empty() {
+                output.push('\n');
+            } else {
+                output.push_str(&format!("  {}\n", line));
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// The config if it lists members, or else the nearest one above it that does, which is then
+/// used from here on, so that members can be used from inside another member
+fn load_workspace() -> Result<Config> {
+    let config = load_config()?;
+    if !config.members.is_empty() {
+        return Ok(config);
+    }
+
+    let current_dir = env::current_dir()?;
+    for dir in current_dir.ancestors().skip(1) {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if !path.is_file() {
+            continue;
+        }
+        let workspace = load_config_from(&path)?;
+        if !workspace.members.is_empty() {
+            env::set_current_dir(dir)?;
+            set_config_path(CONFIG_FILE_NAME.to_string());
+            return Ok(workspace);
+        }
+    }
+    Ok(config)
+}
+
+/// The members given with `--member`, or every member of the workspace
+fn select_members(config: &Config, members: &[String]) -> Result<Vec<String>> {
+    if config.members.is_empty() {
+        return Err(anyhow!("{} doesn't list any members, add their directories under `members`.", CONFIG_FILE_NAME));
+    }
+    if members.is_empty() {
+        return Ok(config.members.clone());
+    }
+
+    if let Some(member) = members.iter().find(|member| !config.members.contains(member)) {
+        return Err(anyhow!("{} is not a member of the workspace.", member));
+    }
+    Ok(members.to_vec())
+}
+
+fn defines_profiles(config: &Config, profiles: &[String]) -> bool {
+    profiles
+        .iter()
+        .all(|profile| config.profiles.contains_key(profile) || config.groups.contains_key(profile))
+}
+
+/// Run something in a member's directory, with its nvy.yaml as the config
+fn in_member<T>(member: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    let workspace_dir = env::current_dir()?;
+    env::set_current_dir(member).map_err(|e| anyhow!("Failed to change to member {}: {}", member, e))?;
+    let workspace_config = set_config_path(CONFIG_FILE_NAME.to_string());
+
+    let result = if does_config_exist() {
+        f()
+    } else {
+        Err(anyhow!("Member {} doesn't have a {}.", member, CONFIG_FILE_NAME))
+    };
+
+    set_config_path(workspace_config);
+    env::set_current_dir(workspace_dir)?;
+    result.with_context(|| format!("Failed in member {}", member))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_members_should_default_to_every_member() {
+        let config: Config = serde_yaml::from_str("members: [api, web]").unwrap();
+        assert_eq!(select_members(&config, &[]).unwrap(), vec!["api", "web"]);
+        assert_eq!(select_members(&config, &["web".to_string()]).unwrap(), vec!["web"]);
+        assert!(select_members(&config, &["docs".to_string()]).is_err());
+    }
+}
@@ -8,6 +8,12 @@ pub struct TestEnv {
     pub temp_dir: TempDir,
 }
 
+impl Default for TestEnv {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestEnv {
     pub fn new() -> Self {
         let temp_dir = TempDir::new().unwrap();
@@ -151,6 +157,121 @@ profiles:
     assert_eq!(contents, expected_config);
 }
 
+#[test]
+fn test_init_preserves_groups_secrets_types_and_members() {
+    let env = TestEnv::new();
+
+    env.create_config(r#"target: .env.local
+profiles:
+  default:
+    - path: .env
+groups:
+  all: [default, prod]
+secrets:
+  - TOKEN
+types:
+  PORT: int
+members:
+  - api"#).unwrap();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("init")
+        .current_dir(&env.temp_dir)
+        .write_stdin("y\n")
+        .assert()
+        .success();
+
+    let contents = env.get_config_contents();
+    let expected_config = r#"target: .env.local
+current_profiles: []
+profiles:
+  default:
+  - path: .env
+  prod:
+  - path: .env.prod
+groups:
+  all:
+  - default
+  - prod
+secrets:
+- TOKEN
+types:
+  PORT: int
+members:
+- api
+"#;
+    assert_eq!(contents, expected_config);
+}
+
+#[test]
+fn test_init_keeps_existing_profiles() {
+    let env = TestEnv::new();
+
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: config/prod.env
+      secret: true
+  staging:
+    - extends: [default]
+    - path: .env.staging
+    - path: .env.staging.local
+  gone:
+    - path: .env.gone
+groups:
+  all: [default, prod]"#).unwrap();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    fs::create_dir(env.temp_dir.path().join("config")).unwrap();
+    env.create_env_file("config/prod.env", "APP_ENV=production").unwrap();
+    env.create_env_file(".env.staging", "APP_ENV=staging").unwrap();
+    env.create_env_file(".env.staging.local", "DEBUG=true").unwrap();
+    env.create_env_file(".env.test", "APP_ENV=test").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("init")
+        .current_dir(&env.temp_dir)
+        .write_stdin("y\n")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Removing profile gone, as its file .env.gone no longer exists."));
+
+    let contents = env.get_config_contents();
+    let expected_config = r#"target: .env.nvy
+current_profiles: []
+profiles:
+  default:
+  - path: .env
+  prod:
+  - path: config/prod.env
+    secret: true
+  staging:
+  - extends:
+    - default
+  - path: .env.staging
+  - path: .env.staging.local
+  test:
+  - path: .env.test
+groups:
+  all:
+  - default
+  - prod
+"#;
+    assert_eq!(contents, expected_config);
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["show", "all"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("APP_ENV=******** (prod"));
+}
+
 #[test]
 fn test_init_ignores_target_env_file() {
     let env = TestEnv::new();
@@ -465,6 +586,57 @@ profiles:
     assert!(output.contains("export ANOTHER_VALID='789'"));
     assert!(!output.contains("INVALID-VAR"));
     assert!(!output.contains("INVALID@VAR"));
+
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains(".env.test:2: invalid variable name INVALID-VAR"));
+    assert!(stderr.contains(".env.test:4: invalid variable name INVALID@VAR"));
+}
+
+#[test]
+fn test_use_warns_about_lines_without_equals() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "# comment\nVALID=1\nNOT_A_VAR\n").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export VALID='1'"))
+        .stdout(predicate::str::contains("NOT_A_VAR").not())
+        .stderr(predicate::str::contains(".env:3: expected KEY=VALUE but found no `=`"));
+}
+
+#[test]
+fn test_use_strict_fails_on_invalid_lines() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env.test", "VALID_VAR=123\nINVALID-VAR=456").unwrap();
+    env.create_env_file(".env.target", "EXISTING=1\n").unwrap();
+    env.create_config(r#"target: .env.target
+current_profiles: []
+profiles:
+  test:
+    - path: .env.test"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("test")
+        .arg("--strict")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".env.test:2: invalid variable name INVALID-VAR"))
+        .stderr(predicate::str::contains("refusing to export in strict mode"));
+
+    let target_contents = fs::read_to_string(env.temp_dir.path().join(".env.target")).unwrap();
+    assert_eq!(target_contents, "EXISTING=1\n");
+    assert!(!env.get_config_contents().contains("- test"));
 }
 
 #[test]
@@ -862,4 +1034,2132 @@ profiles:
     assert!(!contents.contains("test:"));
     assert!(contents.contains("default:"));
     assert!(contents.contains("prod:"));
-}
\ No newline at end of file
+}
+#[test]
+fn test_use_dotenv_syntax_shell() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", r#"export APP_ENV=default # the default env
+PRIVATE_KEY="-----BEGIN KEY-----
+abc\ndef
+-----END KEY-----"
+JSON='{"a": "b"}'
+"#).unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(output.contains("export APP_ENV='default'\n"));
+    assert!(output.contains("export PRIVATE_KEY='-----BEGIN KEY-----\nabc\ndef\n-----END KEY-----'\n"));
+    assert!(output.contains(r#"export JSON='{"a": "b"}'"#));
+}
+
+#[test]
+fn test_use_dotenv_syntax_target() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", r#"export APP_ENV=default # the default env
+PRIVATE_KEY="-----BEGIN KEY-----
+abc
+-----END KEY-----"
+JSON='{"a": "b"}'
+"#).unwrap();
+    
+    env.create_config(r#"target: .env.target
+current_profiles: []
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = fs::read_to_string(env.temp_dir.path().join(".env.target")).unwrap();
+    let expected = r#"# default
+APP_ENV=default
+PRIVATE_KEY="-----BEGIN KEY-----\nabc\n-----END KEY-----"
+JSON='{"a": "b"}'
+"#;
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_interpolates_across_profiles() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "DB_USER=app\nDB_HOST=localhost\nDATABASE_URL=postgres://${DB_USER}@${DB_HOST}/app").unwrap();
+    env.create_env_file(".env.prod", "DB_HOST=db.prod\nLOG_LEVEL=${LEVEL:-info}\nLITERAL='${DB_HOST}'").unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("default")
+        .arg("prod")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(output.contains("export DATABASE_URL='postgres://app@db.prod/app'"));
+    assert!(output.contains("export LOG_LEVEL='info'"));
+    assert!(output.contains("export LITERAL='${DB_HOST}'"));
+}
+
+#[test]
+fn test_use_fails_with_undefined_reference() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "DATABASE_URL=postgres://${DB_HOST}/app").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Variable DB_HOST referenced by DATABASE_URL is not defined"));
+}
+
+#[test]
+fn test_use_fails_with_reference_cycle() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "A=${B}\nB=${A}").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Variable reference cycle detected: A -> B -> A"));
+}
+
+#[test]
+fn test_use_multiple_profiles_overrides_keys_on_different_lines() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env.base", "SHARED=base\nBASE_ONLY=value").unwrap();
+    env.create_env_file(".env.override", "# comment\nOVERRIDE_ONLY=value\nSHARED=override").unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  base:
+    - path: .env.base
+  override:
+    - path: .env.override"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("base")
+        .arg("override")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+
+    let expected = r#"# base
+export BASE_ONLY='value'
+
+# override
+export OVERRIDE_ONLY='value'
+export SHARED='override'
+
+"#;
+    
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_profile_with_multiple_files() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=placeholder").unwrap();
+    env.create_env_file(".env.secrets", "API_KEY=secret").unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+    - path: .env.secrets"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let expected = r#"# default
+export APP_ENV='default'
+export API_KEY='secret'
+
+"#;
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_profile_with_missing_layer_fails() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env
+    - path: .env.secrets"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Provided path .env.secrets under profile default does not exist."));
+}
+
+#[test]
+fn test_config_shows_multiple_files() {
+    let env = TestEnv::new();
+
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env
+    - path: .env.secrets"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("config")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  - default: \".env\", \".env.secrets\"\n"));
+}
+
+#[test]
+fn test_profiles_set_multiple_files() {
+    let env = TestEnv::new();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env"#).unwrap();
+    
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.secrets", "API_KEY=secret").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .arg("set")
+        .arg("secrets")
+        .arg(".env")
+        .arg(".env.secrets")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = env.get_config_contents();
+    let expected = r#"target: sh
+current_profiles: []
+profiles:
+  default:
+  - path: .env
+  secrets:
+  - path: .env
+  - path: .env.secrets
+"#;
+    assert_eq!(actual, expected);
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  - secrets:\n    - .env\n    - .env.secrets\n"));
+}
+
+#[test]
+fn test_use_profile_with_extends() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default\nDB_HOST=localhost").unwrap();
+    env.create_env_file(".env.base", "LOG_LEVEL=info").unwrap();
+    env.create_env_file(".env.staging", "APP_ENV=staging").unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+  base:
+    - path: .env.base
+      extends: [default]
+  staging:
+    - extends: [base]
+    - path: .env.staging"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("staging")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let expected = r#"# default
+export DB_HOST='localhost'
+
+# base
+export LOG_LEVEL='info'
+
+# staging
+export APP_ENV='staging'
+
+"#;
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_profile_with_extends_cycle_fails() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env.a", "A=1").unwrap();
+    env.create_env_file(".env.b", "B=1").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  a:
+    - path: .env.a
+      extends: [b]
+  b:
+    - path: .env.b
+      extends: [a]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("a")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Profile inheritance cycle detected: a -> b -> a"));
+}
+
+#[test]
+fn test_use_unsets_variables_inherited_by_previous_profile() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "DB_HOST=localhost").unwrap();
+    env.create_env_file(".env.staging", "APP_ENV=staging").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production").unwrap();
+    
+    env.create_config(r#"target: sh
+current_profiles: ["staging"]
+profiles:
+  default:
+    - path: .env
+  staging:
+    - path: .env.staging
+      extends: [default]
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("prod")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("unset DB_HOST"))
+        .stdout(predicate::str::contains("export APP_ENV='production'"));
+}
+
+#[test]
+fn test_profiles_set_with_extends() {
+    let env = TestEnv::new();
+    
+    env.create_config(r#"target: sh
+current_profiles: []
+profiles:
+  default:
+    - path: .env"#).unwrap();
+    
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.staging", "APP_ENV=staging").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .arg("set")
+        .arg("staging")
+        .arg(".env.staging")
+        .arg("--extends")
+        .arg("default")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = env.get_config_contents();
+    let expected = r#"target: sh
+current_profiles: []
+profiles:
+  default:
+  - path: .env
+  staging:
+  - extends:
+    - default
+  - path: .env.staging
+"#;
+    assert_eq!(actual, expected);
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("config")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  - staging: \".env.staging\" (extends default)\n"));
+}
+
+#[test]
+fn test_profiles_set_with_self_extends_fails() {
+    let env = TestEnv::new();
+    
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .arg("set")
+        .arg("default")
+        .arg(".env")
+        .arg("--extends")
+        .arg("default")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Profile inheritance cycle detected: default -> default"));
+
+    assert!(!env.get_config_contents().contains("extends"));
+}
+
+#[test]
+fn test_use_group() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_env_file(".env.local", "APP_ENV=local").unwrap();
+    env.create_env_file(".env.secrets-dev", "API_KEY=dev-secret").unwrap();
+    env.create_env_file(".env.target", "").unwrap();
+    
+    env.create_config(r#"target: .env.target
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+  local:
+    - path: .env.local
+  secrets-dev:
+    - path: .env.secrets-dev
+groups:
+  dev: [default, local, secrets-dev]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("dev")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let target_contents = fs::read_to_string(env.temp_dir.path().join(".env.target")).unwrap();
+    let expected = r#"# local
+APP_ENV=local
+
+# secrets-dev
+API_KEY=dev-secret
+"#;
+    assert_eq!(target_contents, expected);
+    
+    let config_contents = env.get_config_contents();
+    assert!(config_contents.contains("current_profiles:\n- default\n- local\n- secrets-dev\ncurrent_groups:\n- dev\n"));
+    assert!(config_contents.contains("groups:\n  dev:\n  - default\n  - local\n  - secrets-dev\n"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("config")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("current: default, local, secrets-dev (group: dev)\n"));
+}
+
+#[test]
+fn test_use_group_with_unknown_member_fails() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env
+groups:
+  dev: [default, missing]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("dev")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Group dev contains missing, which is not a profile"));
+}
+
+#[test]
+fn test_profiles_lists_groups() {
+    let env = TestEnv::new();
+
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env
+  local:
+    - path: .env.local
+groups:
+  dev: [default, local]"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let expected = r#"profiles:
+  - default:
+    - .env
+  - local:
+    - .env.local
+groups:
+  - dev: default, local
+"#;
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_fish_shell() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=it's prod").unwrap();
+    
+    env.create_config(r#"target: fish
+current_profiles: ["default"]
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .arg("prod")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let expected = r#"set -e API_KEY
+set -e APP_ENV
+# prod
+set -gx APP_ENV 'it\'s prod'
+
+"#;
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn test_use_pwsh_shell() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=it's default").unwrap();
+    env.create_config(r#"target: pwsh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("$env:APP_ENV = 'it''s default'\n"));
+
+    assert!(!env.temp_dir.path().join("pwsh").exists());
+}
+
+#[test]
+fn test_use_csh_shell() {
+    let env = TestEnv::new();
+    
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: csh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let actual = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(actual, "setenv APP_ENV 'default';\n\n");
+}
+
+#[test]
+fn test_hook_bash_runs_hook_env_from_prompt_command() {
+    let env = TestEnv::new();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook")
+        .arg("bash")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(output.contains("hook-env bash"));
+    assert!(output.contains("PROMPT_COMMAND"));
+}
+
+#[test]
+fn test_hook_env_exports_current_profiles_on_enter() {
+    let env = TestEnv::new();
+    let config_home = env.temp_dir.path().join("config-home");
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("allow")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .env_remove("NVY_PREVIOUS")
+        .env("API_KEY", "mine")
+        .env_remove("APP_ENV")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(output.contains("export APP_ENV='default'\n"));
+    assert!(output.contains("export API_KEY='123'\n"));
+    assert!(output.contains(r#"export NVY_PREVIOUS='{"API_KEY":"mine","APP_ENV":null}'"#));
+    assert!(output.contains("export NVY_DIR="));
+}
+
+#[test]
+fn test_hook_env_skips_config_that_is_not_allowed() {
+    let env = TestEnv::new();
+    let config_home = env.temp_dir.path().join("config-home");
+
+    env.create_env_file(".env", "PATH=/tmp/evil").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains("is not allowed, run `nvy allow`"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("allow")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    // Changes made by nvy keep the config allowed, but changes made by anything else don't
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["profiles", "set", "other", ".env"])
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export PATH='/tmp/evil'"));
+
+    let config = env.get_config_contents();
+    env.create_config(&format!("{}\n", config)).unwrap();
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_hook_env_skips_config_whose_profile_files_changed() {
+    let env = TestEnv::new();
+    let config_home = env.temp_dir.path().join("config-home");
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("allow")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    // Changes made by nvy keep the config allowed
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["set", "DEBUG=true", "--profile", "default"])
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export DEBUG='true'"));
+
+    env.create_env_file(".env", "PATH=/tmp/evil").unwrap();
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("bash")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env_remove("NVY_DIR")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("")
+        .stderr(predicate::str::contains("is not allowed, run `nvy allow`"));
+}
+
+#[test]
+fn test_hook_env_does_nothing_in_active_directory() {
+    let env = TestEnv::new();
+    let config_home = env.temp_dir.path().join("config-home");
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("allow")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("fish")
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("NVY_DIR", env.temp_dir.path())
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("");
+}
+
+#[test]
+fn test_hook_env_restores_previous_values_on_leave() {
+    let env = TestEnv::new();
+
+    let assert = AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("hook-env")
+        .arg("zsh")
+        .env("NVY_DIR", "/some/project")
+        .env("NVY_PREVIOUS", r#"{"API_KEY":null,"EDITOR":"vim"}"#)
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(output, "unset API_KEY\nexport EDITOR='vim'\nunset NVY_DIR\nunset NVY_PREVIOUS\n");
+}
+
+#[test]
+fn test_exec_runs_command_with_profiles_applied() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["exec", "default", "prod", "--", "sh", "-c", "echo \"$APP_ENV $API_KEY\""])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("production 123\n");
+
+    assert!(!env.temp_dir.path().join(".env.nvy").exists());
+    assert!(env.get_config_contents().contains("current_profiles: []"));
+}
+
+#[test]
+fn test_exec_passes_through_exit_code() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["exec", "default", "--", "sh", "-c", "exit 7"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .code(7);
+}
+
+#[test]
+fn test_exec_fails_without_command() {
+    let env = TestEnv::new();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["exec", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_use_json_target_from_extension() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_config(r#"target: env.json
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join("env.json")).unwrap();
+    assert_eq!(contents, "{\n  \"API_KEY\": \"123\",\n  \"APP_ENV\": \"default\"\n}\n");
+}
+
+#[test]
+fn test_use_configmap_target_from_format() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nPORT=8080").unwrap();
+    env.create_config(r#"target: api-config.yaml
+format: configmap
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join("api-config.yaml")).unwrap();
+    assert_eq!(contents, "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: api-config\ndata:\n  APP_ENV: default\n  PORT: '8080'\n");
+}
+
+#[test]
+fn test_use_secret_and_make_formats() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "PASSWORD='pa$$#word'").unwrap();
+    env.create_config(r#"target: secret.yaml
+format: secret
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join("secret.yaml")).unwrap();
+    assert!(contents.contains("kind: Secret\n"));
+    assert!(contents.contains("type: Opaque\nstringData:\n  PASSWORD: pa$$#word\n"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["target", "set", "env.mk"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    assert!(!env.get_config_contents().contains("format:"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join("env.mk")).unwrap();
+    assert_eq!(contents, "# default\nexport PASSWORD := pa$$$$\\#word\n");
+}
+
+#[test]
+fn test_target_set_with_format() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["target", "set", "app.env", "--format", "systemd"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    assert!(env.get_config_contents().contains("format: systemd"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("target")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("target: app.env\nformat: systemd\n");
+}
+
+#[test]
+fn test_import_json_creates_profile() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file("config.json", r#"{"API_URL": "https://api.example.com", "PORT": 8080, "db": {"host": "localhost"}}"#).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "config.json", "--profile", "api"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env.api")).unwrap();
+    assert_eq!(contents, "API_URL=https://api.example.com\nPORT=8080\ndb_host=localhost\n");
+    assert!(env.get_config_contents().contains("path: .env.api"));
+}
+
+#[test]
+fn test_import_keeps_values_literal_and_existing_profiles() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file("a.json", r#"{"URL": "${HOST}/x", "QUOTE": "it's ${HOST}"}"#).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "a.json", "--profile", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Profile default already exists, pass --force to replace it."));
+    assert!(!env.get_config_contents().contains(".env.default"));
+    assert!(!env.temp_dir.path().join(".env.default").exists());
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "a.json", "--profile", "c"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["show", "c"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("URL=${HOST}/x"))
+        .stdout(predicate::str::contains("QUOTE=it's ${HOST}"));
+}
+
+#[test]
+fn test_import_rejects_empty_and_colliding_keys() {
+    let env = TestEnv::new();
+
+    env.create_env_file("empty.json", r#"{"": "x", "A": "1"}"#).unwrap();
+    env.create_env_file("collide.json", r#"{"a-b": "1", "a": {"b": "2"}}"#).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles: {}"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "empty.json", "--profile", "empty"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("empty.json contains a variable with an empty name"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "collide.json", "--profile", "collide"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("a-b and a_b in collide.json would both be imported as a_b"));
+
+    assert!(!env.temp_dir.path().join(".env.empty").exists());
+    assert!(!env.temp_dir.path().join(".env.collide").exists());
+}
+
+#[test]
+fn test_import_compose_environment() {
+    let env = TestEnv::new();
+
+    env.create_env_file("docker-compose.yml", r#"services:
+  db:
+    image: postgres
+  web:
+    image: web
+    environment:
+      - APP_ENV=production
+      - GREETING=hello world
+      - FROM_HOST
+"#).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles: {}"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "docker-compose.yml", "--profile", "web"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipping FROM_HOST in service web"));
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env.web")).unwrap();
+    assert_eq!(contents, "APP_ENV=production\nGREETING='hello world'\n");
+}
+
+#[test]
+fn test_import_compose_requires_service_when_ambiguous() {
+    let env = TestEnv::new();
+
+    env.create_env_file("compose.yaml", r#"services:
+  api:
+    environment:
+      APP_ENV: api
+  web:
+    environment:
+      APP_ENV: web
+"#).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles: {}"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "compose.yaml", "--profile", "web"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pick one with --service"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "compose.yaml", "--profile", "web", "--service", "web"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env.web")).unwrap();
+    assert_eq!(contents, "APP_ENV=web\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["import", "compose.yaml", "--profile", "web", "--service", "api"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already exists"));
+}
+
+#[test]
+fn test_diff_masks_values_by_default() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.staging", "APP_ENV=staging\nDEBUG=true\nPORT=8080").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production\nPORT=8080\nSENTRY_DSN=secret").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  staging:
+    - path: .env.staging
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["diff", "staging", "prod"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("only in staging:\n  - DEBUG\nonly in prod:\n  - SENTRY_DSN\nchanged:\n  - APP_ENV\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["diff", "staging", "prod", "--show-values"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("only in staging:\n  - DEBUG=true\nonly in prod:\n  - SENTRY_DSN=secret\nchanged:\n  - APP_ENV: staging -> production\n");
+}
+
+#[test]
+fn test_diff_between_groups() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.local", "DEBUG=true").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  local:
+    - path: .env.local
+groups:
+  dev: [default, local]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["diff", "dev", "default,local"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("No differences between dev and default,local.\n");
+}
+
+#[test]
+fn test_status_reports_up_to_date_target() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("use")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("status")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("target: .env.nvy is up to date with default\n");
+}
+
+#[test]
+fn test_status_reports_drifted_keys() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123\nPORT=8080").unwrap();
+    env.create_env_file(".env.nvy", "# default\nAPP_ENV=edited\nPORT=8080\nDEBUG=true\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("status")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("target: .env.nvy has drifted from default\nadded:\n  - DEBUG\nremoved:\n  - API_KEY\nchanged:\n  - APP_ENV\n");
+}
+
+#[test]
+fn test_status_reports_changed_sources() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: sh
+current_profiles:
+- default
+last_used: 1
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("status")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("target: sh (shell)\nchanged since the last `nvy use`:\n  - .env\n");
+}
+
+#[test]
+fn test_status_reports_source_changed_right_after_use() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    env.create_env_file(".env", "APP_ENV=changed").unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("status")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("target: sh (shell)\nchanged since the last `nvy use`:\n  - .env\n");
+}
+
+#[test]
+fn test_check_passes_matching_profiles() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.example", "DATABASE_URL=\nPORT=").unwrap();
+    env.create_env_file(".env", "DATABASE_URL=postgres://localhost/app\nPORT=8080").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+types:
+  DATABASE_URL: url
+  PORT: int"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("check")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Profile(s) default match .env.example"));
+}
+
+#[test]
+fn test_check_fails_on_missing_undeclared_and_invalid_keys() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.example", "DATABASE_URL=\nLOG_LEVEL=").unwrap();
+    env.create_env_file(".env", "DATABASE_URL=postgres://localhost/app\nLOG_LEVEL=info").unwrap();
+    env.create_env_file(".env.prod", "LOG_LEVEL=verbose\nDEBUG=true").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod
+types:
+  LOG_LEVEL: [debug, info, warn]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["check", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["check", "prod"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("prod: DATABASE_URL is missing"))
+        .stderr(predicate::str::contains("prod: DEBUG is not declared in .env.example"))
+        .stderr(predicate::str::contains("prod: LOG_LEVEL is not one of debug, info, warn"))
+        .stderr(predicate::str::contains("Found 3 problem(s)"));
+}
+
+#[test]
+fn test_encrypt_profile_with_identity() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.prod", "API_KEY=secret").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["encrypt", "prod"])
+        .env_remove("NVY_IDENTITY")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let encrypted = fs::read_to_string(env.temp_dir.path().join(".env.prod.age")).unwrap();
+    assert!(encrypted.starts_with("-----BEGIN AGE ENCRYPTED FILE-----"));
+    assert!(!encrypted.contains("secret"));
+    assert!(env.temp_dir.path().join(".nvy/identity.txt").exists());
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".nvy/.gitignore")).unwrap(), "*\n");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = fs::metadata(env.temp_dir.path().join(".nvy/identity.txt")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+    assert!(env.get_config_contents().contains("path: .env.prod.age"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "prod"])
+        .env_remove("NVY_IDENTITY")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap();
+    assert_eq!(contents, "# prod\nAPI_KEY=secret\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "prod"])
+        .env("NVY_IDENTITY", env.temp_dir.path().join("missing.txt"))
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("there is no identity at"));
+}
+
+#[test]
+fn test_encrypt_profile_with_passphrase() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.prod", "API_KEY=secret").unwrap();
+    env.create_config(r#"target: sh
+profiles:
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["encrypt", "prod", "--passphrase"])
+        .env("NVY_PASSPHRASE", "correct horse")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    assert!(!env.temp_dir.path().join(".nvy/identity.txt").exists());
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "prod"])
+        .env_remove("NVY_PASSPHRASE")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("set NVY_PASSPHRASE to decrypt it"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "prod"])
+        .env("NVY_PASSPHRASE", "correct horse")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("export API_KEY='secret'"));
+}
+
+#[test]
+fn test_diff_masks_secret_values() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.staging", "API_KEY=abc # nvy:secret\n# nvy:secret\nDB_PASSWORD=hunter2\nTOKEN=t1\nAPP_ENV=staging").unwrap();
+    env.create_env_file(".env.prod", "API_KEY=def\nDB_PASSWORD=hunter3\nTOKEN=t2\nAPP_ENV=production").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  staging:
+    - path: .env.staging
+  prod:
+    - path: .env.prod
+secrets:
+  - TOKEN"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["diff", "staging", "prod", "--show-values"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("changed:\n  - API_KEY: ******** -> ********\n  - APP_ENV: staging -> production\n  - DB_PASSWORD: ******** -> ********\n  - TOKEN: ******** -> ********\n");
+}
+
+#[test]
+fn test_profiles_set_secret_shows_in_config() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env.prod", "API_KEY=secret").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles: {}"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["profiles", "set", "prod", ".env.prod", "--secret"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    assert!(env.get_config_contents().contains("secret: true"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("config")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("  - prod: \".env.prod\" (secret)\n"));
+
+    // Secret values are still exported, so that they can be evaluated
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["exec", "prod", "--", "sh", "-c", "echo $API_KEY"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("secret\n");
+}
+
+#[test]
+fn test_get_reads_current_or_given_profile() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+- prod
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["get", "APP_ENV"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("production\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["get", "APP_ENV", "--profile", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("default\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["get", "MISSING", "--profile", "prod"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Variable MISSING is not defined in profile(s) prod."));
+}
+
+#[test]
+fn test_set_and_unset_keep_file_formatting() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "# app settings\nexport APP_ENV = 'default' # env\n\nAPI_KEY=123\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["set", "APP_ENV=staging", "--profile", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["set", "NEW_KEY=hello world", "--profile", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env")).unwrap();
+    assert_eq!(contents, "# app settings\nexport APP_ENV = 'staging' # env\n\nAPI_KEY=123\nNEW_KEY=\"hello world\"\n");
+    assert!(!env.temp_dir.path().join(".env.nvy").exists());
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["unset", "API_KEY", "--profile", "default", "--apply"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let contents = fs::read_to_string(env.temp_dir.path().join(".env")).unwrap();
+    assert_eq!(contents, "# app settings\nexport APP_ENV = 'staging' # env\n\nNEW_KEY=\"hello world\"\n");
+
+    let target = fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap();
+    assert_eq!(target, "# default\nAPP_ENV=staging\nNEW_KEY='hello world'\n");
+}
+
+#[test]
+fn test_set_apply_keeps_current_groups() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "A=1\n").unwrap();
+    env.create_env_file(".env.local", "B=1\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  local:
+    - path: .env.local
+groups:
+  dev: [default, local]"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "dev"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["set", "B=2", "--profile", "local", "--apply"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let config = env.get_config_contents();
+    assert!(config.contains("current_groups:\n- dev\n"), "{}", config);
+    assert!(config.contains("current_profiles:\n- default\n- local\n"), "{}", config);
+    let target = fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap();
+    assert!(target.contains("B=2"), "{}", target);
+}
+
+#[test]
+fn test_set_updates_file_that_defines_key() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nAPI_KEY=123").unwrap();
+    env.create_env_file(".env.secrets", "DB_PASSWORD=hunter2").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+    - path: .env.secrets"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["set", "API_KEY=456", "--profile", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Set API_KEY in .env\n"));
+
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env")).unwrap(), "APP_ENV=default\nAPI_KEY=456");
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env.secrets")).unwrap(), "DB_PASSWORD=hunter2");
+}
+
+#[test]
+fn test_keys_rename_across_profiles_after_confirmation() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "# urls\nDB_URL=postgres://localhost # local\nAPP_ENV=default\n").unwrap();
+    env.create_env_file(".env.prod", "export DB_URL='postgres://prod'\n").unwrap();
+    env.create_env_file(".env.test", "APP_ENV=test\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod
+  test:
+    - path: .env.test
+secrets:
+  - DB_URL"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["keys", "rename", "DB_URL", "DATABASE_URL"])
+        .current_dir(&env.temp_dir)
+        .write_stdin("n\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Renaming DB_URL to DATABASE_URL:\n  - .env (line 2)\n  - .env.prod (line 1)\n  - nvy.yaml\n"));
+
+    assert!(fs::read_to_string(env.temp_dir.path().join(".env")).unwrap().contains("DB_URL="));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["keys", "rename", "DB_URL", "DATABASE_URL"])
+        .current_dir(&env.temp_dir)
+        .write_stdin("y\n")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(env.temp_dir.path().join(".env")).unwrap(),
+        "# urls\nDATABASE_URL=postgres://localhost # local\nAPP_ENV=default\n"
+    );
+    assert_eq!(
+        fs::read_to_string(env.temp_dir.path().join(".env.prod")).unwrap(),
+        "export DATABASE_URL='postgres://prod'\n"
+    );
+    assert!(env.get_config_contents().contains("secrets:\n- DATABASE_URL"));
+}
+
+#[test]
+fn test_keys_remove_from_selected_profiles() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "DEBUG=true\nAPP_ENV=default\n").unwrap();
+    env.create_env_file(".env.prod", "DEBUG=false\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["keys", "remove", "DEBUG", "--profile", "prod", "--yes"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed DEBUG from 1 file(s)"));
+
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env")).unwrap(), "DEBUG=true\nAPP_ENV=default\n");
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env.prod")).unwrap(), "");
+}
+
+#[test]
+fn test_keys_remove_drops_secret_and_type() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "TOKEN=https://token.example.com\nPORT=8080\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+secrets:
+  - TOKEN
+types:
+  TOKEN: url
+  PORT: int"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["keys", "remove", "TOKEN", "--yes"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let config = env.get_config_contents();
+    assert!(!config.contains("TOKEN"), "{}", config);
+    assert!(config.contains("PORT: int"), "{}", config);
+}
+
+#[test]
+fn test_keys_rename_fails_when_new_key_exists() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "OLD=1\nNEW=2\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["keys", "rename", "OLD", "NEW", "--yes"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".env already defines NEW"));
+}
+
+#[test]
+fn test_show_prints_provenance_without_writing() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nDEBUG=true").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production\nAPI_KEY=abc # nvy:secret").unwrap();
+    let config = r#"target: .env.nvy
+current_profiles: []
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#;
+    env.create_config(config).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["show", "default", "prod"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("DEBUG=true (default, .env:2)\nAPP_ENV=production (prod, .env.prod:1)\n  - overrides default (default, .env:1)\nAPI_KEY=******** (prod, .env.prod:2)\n");
+
+    assert!(!env.temp_dir.path().join(".env.nvy").exists());
+    assert_eq!(env.get_config_contents(), config);
+}
+
+#[test]
+fn test_show_masks_key_that_is_secret_in_any_profile() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "TOKEN=real-prod-token").unwrap();
+    env.create_env_file(".env.local", "TOKEN=other # nvy:secret").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  local:
+    - path: .env.local"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["show", "default", "local"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("TOKEN=******** (local, .env.local:1)\n  - overrides ******** (default, .env:1)\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["show", "local", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("TOKEN=******** (default, .env:1)\n  - overrides ******** (local, .env.local:1)\n");
+}
+
+#[test]
+fn test_use_dry_run_prints_diff_without_writing() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default\nDEBUG=true").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production\nDEBUG=true").unwrap();
+    env.create_env_file(".env.nvy", "# default\nAPP_ENV=default\nDEBUG=true\n").unwrap();
+    let config = r#"target: .env.nvy
+current_profiles:
+- default
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#;
+    env.create_config(config).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "prod", "--dry-run"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout("--- .env.nvy\n+++ .env.nvy (nvy use prod)\n@@ -1,3 +1,3 @@\n-# default\n-APP_ENV=default\n+# prod\n+APP_ENV=production\n DEBUG=true\n");
+
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap(), "# default\nAPP_ENV=default\nDEBUG=true\n");
+    assert_eq!(env.get_config_contents(), config);
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "default", "--dry-run"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(".env.nvy is already up to date with default\n");
+}
+
+#[test]
+fn test_restore_target_from_backup() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.prod", "APP_ENV=production").unwrap();
+    env.create_env_file(".env.nvy", "HAND=made\n").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  prod:
+    - path: .env.prod"#).unwrap();
+
+    for profile in ["default", "prod"] {
+        AssertCommand::cargo_bin("nvy").unwrap()
+            .args(["use", profile])
+            .current_dir(&env.temp_dir)
+            .assert()
+            .success();
+    }
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["restore", "--list"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("1: .nvy/backups/.env.nvy."))
+        .stdout(predicate::str::contains("\n2: .nvy/backups/.env.nvy."));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("restore")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap(), "# default\nAPP_ENV=default\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["restore", "3"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap(), "HAND=made\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["restore", "9"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("There are only 4 backup(s) of .env.nvy"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_backups_keep_target_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_env_file(".env.nvy", "TOKEN=secret\n").unwrap();
+    fs::set_permissions(env.temp_dir.path().join(".env.nvy"), fs::Permissions::from_mode(0o600)).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "default"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success();
+
+    let backups: Vec<_> = fs::read_dir(env.temp_dir.path().join(".nvy/backups")).unwrap().collect();
+    assert_eq!(backups.len(), 1);
+    let metadata = fs::metadata(backups[0].as_ref().unwrap().path()).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join(".nvy/.gitignore")).unwrap(), "*\n");
+
+    let metadata = fs::metadata(env.temp_dir.path().join(".env.nvy")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+}
+
+#[test]
+fn test_concurrent_profiles_set_keeps_every_profile() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let dir = env.temp_dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                AssertCommand::cargo_bin("nvy").unwrap()
+                    .args(["profiles", "set", &format!("profile{}", i), ".env"])
+                    .current_dir(dir)
+                    .assert()
+                    .success();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let config = env.get_config_contents();
+    for i in 0..8 {
+        assert!(config.contains(&format!("profile{}:", i)), "profile{} is missing from:\n{}", i, config);
+    }
+    let leftovers: Vec<_> = fs::read_dir(env.temp_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "temporary files were left behind: {:?}", leftovers);
+}
+
+#[test]
+fn test_concurrent_profiles_set_and_remove_keep_every_change() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    let mut config = String::from("target: .env.nvy\nprofiles:\n");
+    for i in 0..8 {
+        config.push_str(&format!("  old{}:\n    - path: .env\n", i));
+    }
+    env.create_config(&config).unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .flat_map(|i| {
+            [
+                vec!["profiles".to_string(), "remove".to_string(), format!("old{}", i)],
+                vec!["profiles".to_string(), "set".to_string(), format!("new{}", i), ".env".to_string()],
+            ]
+        })
+        .map(|args| {
+            let dir = env.temp_dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                AssertCommand::cargo_bin("nvy").unwrap()
+                    .args(&args)
+                    .current_dir(dir)
+                    .assert()
+                    .success();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let config = env.get_config_contents();
+    for i in 0..8 {
+        assert!(!config.contains(&format!("old{}:", i)), "old{} is still in:\n{}", i, config);
+        assert!(config.contains(&format!("new{}:", i)), "new{} is missing from:\n{}", i, config);
+    }
+}
+
+#[test]
+fn test_config_is_found_from_subdirectory() {
+    let env = TestEnv::new();
+
+    env.create_env_file(".env", "APP_ENV=default").unwrap();
+    fs::create_dir(env.temp_dir.path().join("api")).unwrap();
+    env.create_env_file("api/.env.api", "PORT=8080").unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["profiles", "set", "api", ".env.api"])
+        .current_dir(env.temp_dir.path().join("api"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Set profile api with path(s) api/.env.api"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "default", "api"])
+        .current_dir(env.temp_dir.path().join("api"))
+        .assert()
+        .success();
+
+    assert!(env.get_config_contents().contains("path: api/.env.api"));
+    assert_eq!(
+        fs::read_to_string(env.temp_dir.path().join(".env.nvy")).unwrap(),
+        "# default\nAPP_ENV=default\n\n# api\nPORT=8080\n"
+    );
+    assert!(!env.temp_dir.path().join("api/.env.nvy").exists());
+}
+
+#[test]
+fn test_config_flag_and_env_var() {
+    let env = TestEnv::new();
+
+    fs::create_dir(env.temp_dir.path().join("project")).unwrap();
+    fs::create_dir(env.temp_dir.path().join("elsewhere")).unwrap();
+    env.create_env_file("project/.env", "APP_ENV=default").unwrap();
+    env.create_env_file("project/nvy.dev.yaml", r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["--config", "../project/nvy.dev.yaml", "use"])
+        .current_dir(env.temp_dir.path().join("elsewhere"))
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(env.temp_dir.path().join("project/.env.nvy")).unwrap(),
+        "# default\nAPP_ENV=default\n"
+    );
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("status")
+        .env("NVY_CONFIG", env.temp_dir.path().join("project/nvy.dev.yaml"))
+        .current_dir(env.temp_dir.path().join("elsewhere"))
+        .assert()
+        .success()
+        .stdout("target: .env.nvy is up to date with default\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["status", "--config", "missing.yaml"])
+        .current_dir(env.temp_dir.path().join("elsewhere"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing.yaml does not exist."));
+}
+
+#[test]
+fn test_use_profile_in_workspace_members() {
+    let env = TestEnv::new();
+
+    env.create_config("members:\n- api\n- web\n- docs").unwrap();
+    for member in ["api", "web", "docs"] {
+        fs::create_dir(env.temp_dir.path().join(member)).unwrap();
+        env.create_env_file(&format!("{}/.env", member), &format!("APP={}", member)).unwrap();
+    }
+    for member in ["api", "web"] {
+        env.create_env_file(&format!("{}/.env.staging", member), &format!("APP={}-staging", member)).unwrap();
+        env.create_env_file(&format!("{}/nvy.yaml", member), r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env
+  staging:
+    - path: .env.staging"#).unwrap();
+    }
+    env.create_env_file("docs/nvy.yaml", r#"target: .env.nvy
+profiles:
+  default:
+    - path: .env"#).unwrap();
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "staging", "--all"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Skipping member docs. It doesn't define staging."));
+    for member in ["api", "web"] {
+        assert_eq!(
+            fs::read_to_string(env.temp_dir.path().join(member).join(".env.nvy")).unwrap(),
+            format!("# staging\nAPP={}-staging\n", member)
+        );
+    }
+    assert!(!env.temp_dir.path().join("docs/.env.nvy").exists());
+
+    // From inside a member, the workspace above it is used
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "default", "--member", "api,docs"])
+        .current_dir(env.temp_dir.path().join("web"))
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join("api/.env.nvy")).unwrap(), "# default\nAPP=api\n");
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join("docs/.env.nvy")).unwrap(), "# default\nAPP=docs\n");
+    assert_eq!(fs::read_to_string(env.temp_dir.path().join("web/.env.nvy")).unwrap(), "# staging\nAPP=web-staging\n");
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .args(["use", "staging", "--member", "docs"])
+        .current_dir(&env.temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("It doesn't define staging."));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("profiles")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("member api:\n  profiles:\n    - default:\n      - .env\n    - staging:\n      - .env.staging\n"))
+        .stdout(predicate::str::contains("member docs:\n  profiles:\n    - default:\n      - .env\n"));
+
+    AssertCommand::cargo_bin("nvy").unwrap()
+        .arg("config")
+        .current_dir(&env.temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("members: api, web, docs\n"))
+        .stdout(predicate::str::contains("member web:\n  target: .env.nvy\n  current: staging\n"));
+}
+
+#[test]
+fn test_target_set_shell_from_subdirectory() {
+    let env = TestEnv::new();
+
+    fs::create_dir(env.temp_dir.path().join("sub")).unwrap();
+    env.create_config(r#"target: .env.nvy
+profiles: {}"#).unwrap();
+
+    for shell in ["fish", "pwsh", "nu", "csh", "sh"] {
+        AssertCommand::cargo_bin("nvy").unwrap()
+            .args(["target", "set", shell])
+            .current_dir(env.temp_dir.path().join("sub"))
+            .assert()
+            .success();
+        assert!(env.get_config_contents().contains(&format!("target: {}\n", shell)));
+    }
+}